@@ -0,0 +1,185 @@
+use crate::{
+    data::{is_name_char, CharacterSheet},
+    prelude::*,
+};
+use serenity::Mentionable;
+
+/// The D&D abilities, which are shown first on character sheets
+const ABILITIES: [&str; 6] = ["str", "dex", "con", "int", "wis", "cha"];
+
+#[poise::command(
+    slash_command,
+    subcommand_required,
+    subcommands("create", "set", "show", "switch", "delete")
+)]
+pub async fn character(_ctx: Context<'_>) -> Result {
+    unreachable!()
+}
+
+/// Create a character. `$stats` in your rolls will refer to them until you switch characters
+#[poise::command(slash_command)]
+pub async fn create(
+    ctx: Context<'_>,
+    #[description = "The character's name"] name: String,
+    #[description = "The campaign the character is in"] campaign: String,
+) -> Result {
+    let name = name.trim();
+    let campaign = campaign.trim();
+    let created = ctx
+        .data()
+        .use_characters_mut(|c| c.create(ctx.author().id, campaign, name));
+    if !created {
+        reply_error!(ctx, "You already have a character in {}.", campaign);
+    }
+
+    ctx.reply(format!(
+        "Created **{name}** for {campaign}. Use `/character set` to fill in their stats."
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Set one of your character's stats, such as `dex` or `prof`
+#[poise::command(slash_command)]
+pub async fn set(
+    ctx: Context<'_>,
+    #[description = "The name of the stat"] stat: String,
+    #[description = "The stat's value"] value: i64,
+    #[description = "The campaign of the character. Defaults to your current character"]
+    #[autocomplete = "autocomplete_campaign"]
+    campaign: Option<String>,
+) -> Result {
+    let stat = stat.trim_start_matches('$').to_ascii_lowercase();
+    if stat.is_empty() || !stat.chars().all(is_name_char) {
+        reply_error!(
+            ctx,
+            "Stat names can only contain letters, numbers and underscores."
+        );
+    }
+
+    let name = ctx.data().use_characters_mut(|c| {
+        let sheet = c.get_mut(ctx.author().id, campaign.as_deref())?;
+        sheet.stats.insert(stat.clone(), value);
+        Some(sheet.name.clone())
+    });
+    let Some(name) = name else {
+        reply_error!(ctx, "You don't have a character in that campaign.");
+    };
+
+    ctx.reply(format!("Set **{name}**'s `{stat}` to `{value}`."))
+        .await?;
+    Ok(())
+}
+
+/// Show a character sheet
+#[poise::command(slash_command)]
+pub async fn show(
+    ctx: Context<'_>,
+    #[description = "Whose character to show. Defaults to you"] member: Option<serenity::User>,
+    #[description = "The campaign of the character. Defaults to their current character"]
+    campaign: Option<String>,
+) -> Result {
+    let user = member.as_ref().unwrap_or_else(|| ctx.author());
+    let sheet = ctx
+        .data()
+        .use_characters(|c| c.get(user.id, campaign.as_deref()).cloned());
+    let Some(sheet) = sheet else {
+        reply_error!(
+            ctx,
+            "{} doesn't have a character in that campaign.",
+            user.name
+        );
+    };
+
+    let embed = character_card(&sheet).color(ctx.data().bot_color());
+    ctx.send(poise::CreateReply::default().embed(embed).reply(true))
+        .await?;
+    Ok(())
+}
+
+/// Switch to your character in another campaign
+#[poise::command(slash_command, rename = "use")]
+pub async fn switch(
+    ctx: Context<'_>,
+    #[description = "The campaign of the character"]
+    #[autocomplete = "autocomplete_campaign"]
+    campaign: String,
+) -> Result {
+    let switched = ctx
+        .data()
+        .use_characters_mut(|c| c.set_active(ctx.author().id, &campaign));
+    if !switched {
+        reply_error!(ctx, "You don't have a character in {}.", campaign);
+    }
+
+    ctx.reply(format!("Switched to your character in {campaign}."))
+        .await?;
+    Ok(())
+}
+
+/// Delete one of your characters
+#[poise::command(slash_command)]
+pub async fn delete(
+    ctx: Context<'_>,
+    #[description = "The campaign of the character"]
+    #[autocomplete = "autocomplete_campaign"]
+    campaign: String,
+) -> Result {
+    let deleted = ctx
+        .data()
+        .use_characters_mut(|c| c.delete(ctx.author().id, &campaign));
+    let Some(sheet) = deleted else {
+        reply_error!(ctx, "You don't have a character in {}.", campaign);
+    };
+
+    ctx.reply(format!("Deleted **{}**.", sheet.name)).await?;
+    Ok(())
+}
+
+fn character_card(sheet: &CharacterSheet) -> serenity::CreateEmbed {
+    const MAX_FIELDS: usize = 25;
+
+    let (mut abilities, others): (Vec<_>, Vec<_>) = sheet
+        .stats
+        .iter()
+        .partition(|(stat, _)| ABILITIES.contains(&stat.as_str()));
+    abilities.sort_by_key(|(stat, _)| {
+        ABILITIES
+            .iter()
+            .position(|&ability| ability == stat.as_str())
+    });
+
+    let fields = abilities.into_iter().chain(others).map(|(stat, &value)| {
+        // Ability scores are shown with the modifier they give, unless it was set separately
+        let modifier = format!("{stat}_mod");
+        let is_ability = ABILITIES.contains(&stat.as_str());
+        let value = if is_ability && !sheet.stats.contains_key(&modifier) {
+            format!("{value} ({:+})", sheet.stat(&modifier).unwrap())
+        } else {
+            value.to_string()
+        };
+        (stat.to_ascii_uppercase(), value, true)
+    });
+
+    serenity::CreateEmbed::default()
+        .title(sheet.name.clone())
+        .description(format!(
+            "{} • played by {}",
+            sheet.campaign,
+            serenity::UserId::new(sheet.owner).mention()
+        ))
+        .fields(fields.take(MAX_FIELDS))
+}
+
+async fn autocomplete_campaign<'a>(
+    ctx: Context<'_>,
+    partial: &'a str,
+) -> impl Iterator<Item = String> + 'a {
+    let campaigns: Vec<_> = ctx
+        .data()
+        .use_characters(|c| c.campaigns(ctx.author().id).map(str::to_owned).collect());
+    let partial = partial.to_ascii_lowercase();
+    campaigns
+        .into_iter()
+        .filter(move |campaign| campaign.to_ascii_lowercase().starts_with(&partial))
+}
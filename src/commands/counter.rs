@@ -1,6 +1,6 @@
-use crate::prelude::*;
+use crate::{interactive::Paginator, prelude::*};
 
-#[poise::command(slash_command, subcommands("add", "get", "create", "delete"))]
+#[poise::command(slash_command, subcommands("add", "get", "create", "delete", "list"))]
 pub async fn counter(
     ctx: Context<'_>,
     #[autocomplete = "autocomplete_name"] name: String,
@@ -85,7 +85,27 @@ pub async fn delete(
     Ok(())
 }
 
-async fn autocomplete_name<'a>(
+/// List every counter and its value
+#[poise::command(slash_command)]
+pub async fn list(ctx: ApplicationContext<'_>) -> Result {
+    const COUNTERS_PER_PAGE: usize = 15;
+
+    let mut counters: Vec<_> = ctx.data().use_counters(|c| {
+        c.names()
+            .map(|name| (name.to_owned(), c.get(name).unwrap()))
+            .collect()
+    });
+    counters.sort_unstable();
+
+    let lines = counters
+        .into_iter()
+        .map(|(name, value)| format!("`{name}`: `{value}`"));
+    Paginator::from_lines(ctx, "Counters", lines, COUNTERS_PER_PAGE)
+        .send(ctx)
+        .await
+}
+
+pub(in crate::commands) async fn autocomplete_name<'a>(
     ctx: Context<'_>,
     partial: &'a str,
 ) -> impl Iterator<Item = String> + 'a {
@@ -1,147 +1,1805 @@
-use crate::prelude::*;
-use parse_display::FromStr;
+use super::{
+    group_roll::{contest, pool},
+    wildsea::WildseaResult,
+};
+use crate::{
+    data::{day_number, is_name_char, Data, GameMaster, MacroOwner},
+    dice::{render, seeded, Budget, Distribution, Expr, Roll, RolledKind},
+    interactive::{
+        Config as InteractiveConfig, ControlFlow, EndReason, Interaction, Interactive,
+        InteractiveMessage, Paginator, Permission, SessionContext,
+    },
+    prelude::*,
+};
 use rand::Rng;
+use serenity::Mentionable;
+use std::{collections::HashMap, fmt::Write, time::Duration};
 
-#[derive(FromStr, Debug, Copy, Clone, Eq, PartialEq)]
-enum WildseaRoll {
-    #[display("{dice} cut {cut}")]
-    Cut {
-        dice: u32,
-        cut: u32,
+/// The custom IDs of the buttons on a roll's result start with this
+const REROLL_ID_PREFIX: &str = "reroll_";
+/// Discord's limit on the length of a component's custom ID
+const MAX_CUSTOM_ID_LEN: usize = 100;
+/// How long the GM can reveal a secret roll for
+const SECRET_ROLL_DURATION: Duration = Duration::from_secs(60 * 60);
+/// Leaves room for a line about the roll under its result
+const MAX_RESULT_LEN: usize = serenity::constants::MESSAGE_CODE_LIMIT - 100;
+/// The most `[[inline rolls]]` that are rolled from one message
+const MAX_INLINE_ROLLS: usize = 5;
+
+/// Rolls dice in XdY format
+#[poise::command(slash_command, prefix_command)]
+pub async fn roll(
+    ctx: Context<'_>,
+    #[description = "The dice to roll"]
+    #[rest]
+    dice: Option<String>,
+    #[description = "The roll will be visible only to you"]
+    #[flag]
+    private: bool,
+    #[description = "Show the dice as an image"]
+    #[flag]
+    image: bool,
+) -> Result {
+    // `#[rest]` takes everything after the command name, leaving nothing for the flags
+    let (dice, private, image) = match (ctx, dice) {
+        (poise::Context::Prefix(_), Some(dice)) => {
+            let (dice, prefix_private, prefix_image) = split_flags(&dice);
+            let dice = (!dice.is_empty()).then(|| dice.to_owned());
+            (dice, private || prefix_private, image || prefix_image)
+        }
+        (_, dice) => (dice, private, image),
+    };
+    roll_inner(ctx, dice, private, image).await
+}
+
+/// Takes the `private` and `image` flags off the end of a prefix command's dice, like
+/// `!roll 1d20 private`
+fn split_flags(mut dice: &str) -> (&str, bool, bool) {
+    let (mut private, mut image) = (false, false);
+    loop {
+        dice = dice.trim_end();
+        let (rest, word) = dice.rsplit_once(char::is_whitespace).unwrap_or(("", dice));
+        match word {
+            "private" => private = true,
+            "image" => image = true,
+            _ => return (dice, private, image),
+        }
+        dice = rest;
+    }
+}
+
+// Plain rolls stay in `/roll`, since Discord doesn't let a command with subcommands be used on its
+// own. Unlike `/roll`, these are slash commands only.
+/// More dice commands
+#[poise::command(
+    slash_command,
+    subcommand_required,
+    subcommands(
+        "blades",
+        "pbta",
+        "fate",
+        "wod",
+        "pool",
+        "contest",
+        "verify",
+        "secret",
+        "set_gm",
+        "odds",
+        "stats",
+        "save_macro",
+        "roll_macro",
+        "list_macros",
+        "delete_macro"
+    )
+)]
+pub async fn dice(_ctx: Context<'_>) -> Result {
+    unreachable!()
+}
+
+async fn roll_inner(ctx: Context<'_>, dice: Option<String>, private: bool, image: bool) -> Result {
+    let dice = dice.unwrap_or_else(|| String::from("1d20"));
+    let dice = match expand_dice(ctx, &dice) {
+        Ok(dice) => dice,
+        Err(err) => reply_error!(ctx, "Error: {}", err),
+    };
+    // Every roll is seeded so that it can be verified, so expressions that Patbot's own engine
+    // doesn't understand can't be rolled
+    let expr: Expr = match dice.parse() {
+        Ok(expr) => expr,
+        Err(err) => reply_error!(ctx, "Couldn't read `{}`: {}.", dice, err),
+    };
+    let kind = FairRollKind::Dice {
+        expression: expr.to_string(),
+    };
+    let (id, Rolled::Dice(roll)) = fair_roll(ctx.data(), ctx.author().id, kind, private) else {
+        unreachable!()
+    };
+    let (result, image) = roll_result(&format!("`{expr}`"), &roll, image);
+    let content = format!("{result}\n-# Roll #{id}");
+
+    let record = RollRecord {
+        expression: dice,
+        total: roll.total,
+        at: time::OffsetDateTime::now_utc(),
+        user: ctx.author().id.get(),
+        channel: ctx.channel_id().get(),
+    };
+    ctx.data().use_rolls_mut(|rolls| rolls.record(record));
+
+    let mut reply = poise::CreateReply::default()
+        .content(content)
+        .ephemeral(private)
+        .reply(true);
+    // Private rolls don't get buttons, since the new results would be public
+    let buttons = if private { None } else { reroll_buttons(&expr) };
+    if let Some(buttons) = buttons {
+        reply = reply.components(buttons);
+    }
+    if let Some(image) = image {
+        reply = reply.attachment(image);
+    }
+    ctx.send(reply).await?;
+
+    Ok(())
+}
+
+/// Cuts off a roll's result if it's too long to send, keeping the total
+fn fit_message(result: String, total: i64) -> String {
+    if result.len() <= MAX_RESULT_LEN {
+        return result;
+    }
+    format!("{}... = `{total}`", &result[..MAX_RESULT_LEN - 30])
+}
+
+/// Shows a roll after its label. If it's too long to send, or `image` is true, the dice are drawn
+/// in an image instead, unless there are too many of them to draw.
+fn roll_result(
+    label: &str,
+    roll: &Roll,
+    image: bool,
+) -> (String, Option<serenity::CreateAttachment>) {
+    let result = format!("{label}: {}", format_roll(roll));
+    if result.len() <= MAX_RESULT_LEN && !image {
+        return (result, None);
+    }
+    match render::render(roll) {
+        Some(png) => (
+            format!("{label} = **{}**", roll.total),
+            Some(serenity::CreateAttachment::bytes(png, "roll.png")),
+        ),
+        None => (fit_message(result, roll.total), None),
+    }
+}
+
+/// Rolls with the RNG for the next roll ID, and logs the result so that it can be verified. Only
+/// whoever made a private roll can verify it.
+pub(in crate::commands) fn fair_roll(
+    data: &Data,
+    user: serenity::UserId,
+    kind: FairRollKind,
+    private: bool,
+) -> (u64, Rolled) {
+    roll_seeded(data, user, kind, private, None)
+}
+
+/// Rolls like [`fair_roll`] for a secret roll, which only `game_master` can see or verify
+fn secret_fair_roll(
+    data: &Data,
+    user: serenity::UserId,
+    kind: FairRollKind,
+    game_master: GameMaster,
+) -> (u64, Rolled) {
+    roll_seeded(data, user, kind, true, Some(game_master))
+}
+
+fn roll_seeded(
+    data: &Data,
+    user: serenity::UserId,
+    kind: FairRollKind,
+    private: bool,
+    secret_for: Option<GameMaster>,
+) -> (u64, Rolled) {
+    let at = time::OffsetDateTime::now_utc();
+    let (id, mut rng) = data.use_fairness_mut(|fairness| fairness.next_roll(at));
+    let rolled = kind.roll(&mut rng);
+    data.log_fair_roll(FairRoll {
+        id,
+        kind,
+        dice: rolled.values(),
+        at,
+        user: user.get(),
+        private,
+        secret_for,
+    });
+    (id, rolled)
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct FairRoll {
+    pub id: u64,
+    pub kind: FairRollKind,
+    /// The value of each die, in the order they were shown
+    pub dice: Vec<i32>,
+    pub at: time::OffsetDateTime,
+    pub user: u64,
+    #[serde(default)]
+    pub private: bool,
+    /// The GM of a secret roll
+    #[serde(default)]
+    pub secret_for: Option<GameMaster>,
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub enum FairRollKind {
+    /// An expression that Patbot's own engine understands
+    Dice {
+        expression: String,
     },
-    #[display("{dice}")]
-    NoCut {
+    Wildsea {
         dice: u32,
+        cut: u32,
     },
+    Blades(Blades),
+    Pbta(Pbta),
+    Fate(Fate),
+    Wod(Wod),
 }
 
-impl WildseaRoll {
-    pub fn into_parts(self) -> (u32, u32) {
+impl FairRollKind {
+    fn roll(&self, rng: &mut impl Rng) -> Rolled {
         match self {
-            Self::Cut { dice, cut } => (dice, cut),
-            Self::NoCut { dice } => (dice, 0),
+            Self::Dice { expression } => {
+                let expr: Expr = expression.parse().unwrap();
+                Rolled::Dice(expr.roll(rng))
+            }
+            &Self::Wildsea { dice, cut } => Rolled::Wildsea(WildseaResult::roll(dice, cut, rng)),
+            Self::Blades(system) => Rolled::System(system.roll(rng)),
+            Self::Pbta(system) => Rolled::System(system.roll(rng)),
+            Self::Fate(system) => Rolled::System(system.roll(rng)),
+            Self::Wod(system) => Rolled::System(system.roll(rng)),
         }
     }
 }
 
-/// Rolls dice in XdY format
+impl std::fmt::Display for FairRollKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Dice { expression } => write!(f, "`{expression}`"),
+            Self::Wildsea { dice, cut: 0 } => write!(f, "Wildsea, {dice} dice"),
+            Self::Wildsea { dice, cut } => write!(f, "Wildsea, {dice} dice cut {cut}"),
+            Self::Blades(system) => write!(f, "{}, {}", Blades::NAME, system.describe()),
+            Self::Pbta(system) => write!(f, "{}, {}", Pbta::NAME, system.describe()),
+            Self::Fate(system) => write!(f, "{}, {}", Fate::NAME, system.describe()),
+            Self::Wod(system) => write!(f, "{}, {}", Wod::NAME, system.describe()),
+        }
+    }
+}
+
+/// What a [`FairRoll`] rolled
+pub(in crate::commands) enum Rolled {
+    Dice(Roll),
+    Wildsea(WildseaResult),
+    System(SystemResult),
+}
+
+impl Rolled {
+    /// The value of each die, which is what's logged and compared to verify the roll
+    fn values(&self) -> Vec<i32> {
+        match self {
+            Self::Dice(roll) => roll
+                .terms
+                .iter()
+                .filter_map(|term| match &term.kind {
+                    RolledKind::Dice { rolls, .. } => Some(rolls),
+                    RolledKind::Constant(_) => None,
+                })
+                .flatten()
+                .map(|roll| roll.value as i32)
+                .collect(),
+            Self::Wildsea(result) => result.rolls().iter().map(|&roll| roll as i32).collect(),
+            Self::System(result) => result.values.clone(),
+        }
+    }
+}
+
+/// The result as shown in Discord
+impl std::fmt::Display for Rolled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Dice(roll) => f.write_str(&format_roll(roll)),
+            Self::Wildsea(result) => write!(f, "{result}"),
+            Self::System(result) => write!(f, "{} → {}", result.dice, result.reading.title),
+        }
+    }
+}
+
+/// A TTRPG system's way of rolling dice and reading what they mean
+trait DiceSystem {
+    /// The system's name, shown above its results
+    const NAME: &'static str;
+
+    /// What's being rolled, like `3 dice`
+    fn describe(&self) -> String;
+
+    fn roll_dice(&self, rng: &mut impl Rng) -> Vec<i32>;
+
+    fn read(&self, dice: &[i32]) -> Reading;
+
+    fn dice_text(&self, dice: &[i32]) -> String {
+        let dice: Vec<_> = dice.iter().map(i32::to_string).collect();
+        dice.join(" ")
+    }
+
+    fn roll(&self, rng: &mut impl Rng) -> SystemResult {
+        let dice = self.roll_dice(rng);
+        SystemResult {
+            system: Self::NAME,
+            dice: self.dice_text(&dice),
+            reading: self.read(&dice),
+            values: dice,
+        }
+    }
+}
+
+/// How well a roll went, which decides the colour of its result
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Tier {
+    Critical,
+    Success,
+    Partial,
+    Failure,
+}
+
+impl Tier {
+    fn color(self) -> serenity::Color {
+        match self {
+            Self::Critical => serenity::Color::BLUE,
+            Self::Success => serenity::Color::DARK_GREEN,
+            Self::Partial => serenity::Color::GOLD,
+            Self::Failure => serenity::Color::RED,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Reading {
+    tier: Tier,
+    title: String,
+    meaning: &'static str,
+}
+
+pub(in crate::commands) struct SystemResult {
+    system: &'static str,
+    dice: String,
+    reading: Reading,
+    values: Vec<i32>,
+}
+
+/// A pool of d6s, reading the highest. With no dice, two are rolled and the lowest is read.
+#[derive(Debug, Copy, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Blades {
+    dice: u32,
+}
+
+impl DiceSystem for Blades {
+    const NAME: &'static str = "Blades in the Dark";
+
+    fn describe(&self) -> String {
+        format!("{} dice", self.dice)
+    }
+
+    fn roll_dice(&self, rng: &mut impl Rng) -> Vec<i32> {
+        let count = if self.dice == 0 { 2 } else { self.dice };
+        (0..count).map(|_| seeded::die(rng, 6) as i32).collect()
+    }
+
+    fn read(&self, dice: &[i32]) -> Reading {
+        let result = if self.dice == 0 {
+            *dice.iter().min().unwrap()
+        } else {
+            *dice.iter().max().unwrap()
+        };
+        let sixes = dice.iter().filter(|&&die| die == 6).count();
+        let (tier, title, meaning) = match result {
+            6 if self.dice > 0 && sixes >= 2 => (
+                Tier::Critical,
+                "Critical success!",
+                "You do it, with increased effect.",
+            ),
+            6 => (Tier::Success, "Full success!", "You do it."),
+            4 | 5 => (
+                Tier::Partial,
+                "Partial success",
+                "You do it, but there's a consequence: harm, a complication, reduced effect or a \
+                worse position.",
+            ),
+            _ => (
+                Tier::Failure,
+                "Bad outcome",
+                "Things go badly. You probably don't reach your goal, and you suffer a \
+                consequence.",
+            ),
+        };
+        Reading {
+            tier,
+            title: title.to_owned(),
+            meaning,
+        }
+    }
+
+    fn dice_text(&self, dice: &[i32]) -> String {
+        let dice: Vec<_> = dice.iter().map(i32::to_string).collect();
+        if self.dice == 0 {
+            format!("{} (lowest of two)", dice.join(" "))
+        } else {
+            dice.join(" ")
+        }
+    }
+}
+
+/// Powered by the Apocalypse moves: 2d6 plus a stat
+#[derive(Debug, Copy, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Pbta {
+    stat: i32,
+}
+
+impl DiceSystem for Pbta {
+    const NAME: &'static str = "Powered by the Apocalypse";
+
+    fn describe(&self) -> String {
+        format!("2d6{:+}", self.stat)
+    }
+
+    fn roll_dice(&self, rng: &mut impl Rng) -> Vec<i32> {
+        (0..2).map(|_| seeded::die(rng, 6) as i32).collect()
+    }
+
+    fn read(&self, dice: &[i32]) -> Reading {
+        let total = dice.iter().sum::<i32>() + self.stat;
+        let (tier, title, meaning) = match total {
+            10.. => (Tier::Success, "Strong hit!", "You do it, and it goes well."),
+            7..=9 => (
+                Tier::Partial,
+                "Weak hit",
+                "You do it, but with a cost, a hard choice or a complication.",
+            ),
+            _ => (
+                Tier::Failure,
+                "Miss",
+                "The MC makes a move, as hard as they like.",
+            ),
+        };
+        Reading {
+            tier,
+            title: format!("{title} ({total})"),
+            meaning,
+        }
+    }
+
+    fn dice_text(&self, dice: &[i32]) -> String {
+        format!("{} {} {:+}", dice[0], dice[1], self.stat)
+    }
+}
+
+/// Four Fate dice plus a skill, against an opposition
+#[derive(Debug, Copy, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Fate {
+    skill: i32,
+    opposition: i32,
+}
+
+impl Fate {
+    /// The name of a result on the Fate ladder
+    fn ladder(total: i32) -> &'static str {
+        match total {
+            8.. => "Legendary",
+            7 => "Epic",
+            6 => "Fantastic",
+            5 => "Superb",
+            4 => "Great",
+            3 => "Good",
+            2 => "Fair",
+            1 => "Average",
+            0 => "Mediocre",
+            -1 => "Poor",
+            _ => "Terrible",
+        }
+    }
+}
+
+impl DiceSystem for Fate {
+    const NAME: &'static str = "Fate";
+
+    fn describe(&self) -> String {
+        format!("4dF{:+} against {:+}", self.skill, self.opposition)
+    }
+
+    fn roll_dice(&self, rng: &mut impl Rng) -> Vec<i32> {
+        (0..4).map(|_| seeded::die(rng, 3) as i32 - 2).collect()
+    }
+
+    fn read(&self, dice: &[i32]) -> Reading {
+        let total = dice.iter().sum::<i32>() + self.skill;
+        let (tier, title, meaning) = match total - self.opposition {
+            3.. => (
+                Tier::Critical,
+                "Success with style!",
+                "You get what you wanted, and an extra benefit like a boost.",
+            ),
+            1..=2 => (Tier::Success, "Success", "You get what you wanted."),
+            0 => (
+                Tier::Partial,
+                "Tie",
+                "You get what you wanted at a minor cost, or a lesser version of it.",
+            ),
+            _ => (
+                Tier::Failure,
+                "Failure",
+                "You don't get what you wanted, or you get it at a serious cost.",
+            ),
+        };
+        Reading {
+            tier,
+            title: format!("{title}: {} ({total:+})", Self::ladder(total)),
+            meaning,
+        }
+    }
+
+    fn dice_text(&self, dice: &[i32]) -> String {
+        let dice: Vec<_> = dice
+            .iter()
+            .map(|die| match die.signum() {
+                1 => "`+`",
+                -1 => "`-`",
+                _ => "`0`",
+            })
+            .collect();
+        format!("{} {:+}", dice.join(" "), self.skill)
+    }
+}
+
+/// World of Darkness: a pool of d10s, where each die at or over the difficulty is a success and
+/// each 1 cancels one out
+#[derive(Debug, Copy, Clone, serde::Serialize, serde::Deserialize)]
+pub struct Wod {
+    dice: u32,
+    difficulty: u32,
+}
+
+impl DiceSystem for Wod {
+    const NAME: &'static str = "World of Darkness";
+
+    fn describe(&self) -> String {
+        format!("{} dice at difficulty {}", self.dice, self.difficulty)
+    }
+
+    fn roll_dice(&self, rng: &mut impl Rng) -> Vec<i32> {
+        (0..self.dice)
+            .map(|_| seeded::die(rng, 10) as i32)
+            .collect()
+    }
+
+    fn read(&self, dice: &[i32]) -> Reading {
+        let difficulty = self.difficulty as i32;
+        let successes = dice.iter().filter(|&&die| die >= difficulty).count() as i64;
+        let ones = dice.iter().filter(|&&die| die == 1).count() as i64;
+        let net = successes - ones;
+        let (tier, meaning) = match net {
+            _ if successes == 0 && ones > 0 => {
+                (Tier::Failure, "Something goes disastrously wrong.")
+            }
+            ..=0 => (Tier::Failure, "You don't manage it."),
+            1 => (Tier::Partial, "A marginal success: you barely manage it."),
+            2 => (Tier::Success, "A moderate success."),
+            3 => (Tier::Success, "A complete success."),
+            4 => (Tier::Critical, "An exceptional success."),
+            _ => (Tier::Critical, "A phenomenal success!"),
+        };
+        let title = match net {
+            _ if successes == 0 && ones > 0 => String::from("Botch!"),
+            ..=0 => String::from("Failure"),
+            1 => String::from("1 success"),
+            _ => format!("{net} successes"),
+        };
+        Reading {
+            tier,
+            title,
+            meaning,
+        }
+    }
+
+    fn dice_text(&self, dice: &[i32]) -> String {
+        let difficulty = self.difficulty as i32;
+        let dice: Vec<_> = dice
+            .iter()
+            .map(|&die| match die {
+                1 => format!("~~{die}~~"),
+                _ if die >= difficulty => format!("**{die}**"),
+                _ => die.to_string(),
+            })
+            .collect();
+        dice.join(" ")
+    }
+}
+
+/// Rolls a Blades in the Dark dice pool
 #[poise::command(slash_command)]
-pub async fn roll(
+pub async fn blades(
     ctx: Context<'_>,
-    #[description = "The dice to roll"]
-    #[rest]
-    dice: Option<String>,
-    #[description = "The roll will be visible only to you"]
-    #[flag]
-    private: bool,
+    #[description = "How many dice to roll. With none, you roll two and take the lowest"]
+    #[max = 10]
+    dice: u32,
 ) -> Result {
-    let dice = dice.unwrap_or_else(|| String::from("1d20"));
-    let roll = match rust_dice::roll(&dice) {
-        Ok(roll) => roll,
-        Err(err) => {
-            ctx.send(
-                poise::CreateReply::default()
-                    .content(format!("Error: {err}"))
-                    .ephemeral(true)
-                    .reply(true),
+    roll_system(ctx, FairRollKind::Blades(Blades { dice })).await
+}
+
+/// Rolls 2d6 plus a stat for a Powered by the Apocalypse move
+#[poise::command(slash_command)]
+pub async fn pbta(
+    ctx: Context<'_>,
+    #[description = "The stat you're rolling with. Defaults to 0"]
+    #[min = -5]
+    #[max = 5]
+    stat: Option<i32>,
+) -> Result {
+    let stat = stat.unwrap_or(0);
+    roll_system(ctx, FairRollKind::Pbta(Pbta { stat })).await
+}
+
+/// Rolls four Fate dice plus a skill
+#[poise::command(slash_command)]
+pub async fn fate(
+    ctx: Context<'_>,
+    #[description = "Your skill's rating. Defaults to 0"]
+    #[min = -4]
+    #[max = 10]
+    skill: Option<i32>,
+    #[description = "The opposition or difficulty to beat. Defaults to 0"]
+    #[min = -4]
+    #[max = 10]
+    opposition: Option<i32>,
+) -> Result {
+    let fate = Fate {
+        skill: skill.unwrap_or(0),
+        opposition: opposition.unwrap_or(0),
+    };
+    roll_system(ctx, FairRollKind::Fate(fate)).await
+}
+
+/// Rolls a World of Darkness dice pool, counting successes
+#[poise::command(slash_command)]
+pub async fn wod(
+    ctx: Context<'_>,
+    #[description = "How many dice to roll"]
+    #[min = 1]
+    #[max = 20]
+    dice: u32,
+    #[description = "The lowest roll that counts as a success. Defaults to 6"]
+    #[min = 2]
+    #[max = 10]
+    difficulty: Option<u32>,
+) -> Result {
+    let wod = Wod {
+        dice,
+        difficulty: difficulty.unwrap_or(6),
+    };
+    roll_system(ctx, FairRollKind::Wod(wod)).await
+}
+
+async fn roll_system(ctx: Context<'_>, kind: FairRollKind) -> Result {
+    let (id, Rolled::System(result)) = fair_roll(ctx.data(), ctx.author().id, kind, false) else {
+        unreachable!()
+    };
+
+    let embed = serenity::CreateEmbed::default()
+        .color(result.reading.tier.color())
+        .author(serenity::CreateEmbedAuthor::new(result.system))
+        .title(result.reading.title)
+        .description(result.reading.meaning)
+        .field("Dice", result.dice, false)
+        .footer(serenity::CreateEmbedFooter::new(format!("Roll #{id}")));
+    ctx.send(poise::CreateReply::default().embed(embed).reply(true))
+        .await?;
+    Ok(())
+}
+
+/// Checks a roll against the seed it was made with. Without an ID, this shows the seeds instead.
+#[poise::command(slash_command)]
+pub async fn verify(
+    ctx: Context<'_>,
+    #[description = "The roll's ID, shown under it"] id: Option<u64>,
+) -> Result {
+    const FIELD_LIMIT: usize = 1024;
+
+    let now = time::OffsetDateTime::now_utc();
+    let today = day_number(now);
+    let embed = serenity::CreateEmbed::default().color(ctx.data().bot_color());
+
+    let Some(id) = id else {
+        let (commitment, yesterday) = ctx.data().use_fairness(|fairness| {
+            let commitment = fairness.commitment(today);
+            (commitment, fairness.revealed_seed(today - 1, now))
+        });
+        // Today's seed is only created here if nobody has rolled yet today
+        let commitment = match commitment {
+            Some(commitment) => commitment,
+            None => ctx
+                .data()
+                .use_fairness_mut(|fairness| fairness.commit(today)),
+        };
+        let yesterday = yesterday.map_or_else(
+            || String::from("No rolls were made yesterday."),
+            |seed| format!("`{}`", seeded::to_hex(&seed)),
+        );
+        let embed = embed
+            .title("Roll seeds")
+            .description(
+                "Each roll's RNG is seeded with SHA-256 of the day's seed followed by the roll's \
+                ID, as 8 little-endian bytes. Each day's seed is revealed after midnight UTC, so \
+                you can check that it matches the hash published before it was used.",
+            )
+            .field(
+                "Hash of today's seed",
+                format!("`{}`", seeded::to_hex(&commitment)),
+                false,
             )
+            .field("Yesterday's seed", yesterday, false);
+        ctx.send(poise::CreateReply::default().embed(embed).reply(true))
             .await?;
-            return Ok(());
+        return Ok(());
+    };
+
+    let Some(roll) = ctx.data().use_fair_roll_log(|log| log.get(id).cloned()) else {
+        reply_error!(ctx, "There is no roll #{}, or it's too old to verify.", id);
+    };
+    if let Some(game_master) = roll.secret_for {
+        if !is_game_master(ctx, game_master).await {
+            reply_error!(
+                ctx,
+                "Roll #{} was secret, so only {} can verify it.",
+                id,
+                game_master.mention()
+            );
+        }
+    } else if roll.private && roll.user != ctx.author().id.get() {
+        reply_error!(
+            ctx,
+            "Roll #{} was private, so only whoever rolled it can verify it.",
+            id
+        );
+    }
+    let truncate = |s: &str| {
+        if s.len() <= FIELD_LIMIT {
+            s.to_owned()
+        } else {
+            format!("{}...", &s[..FIELD_LIMIT - 3])
         }
     };
 
-    let mut stringify = rust_dice::fmt::MarkdownStringifier::new();
-    match stringify.stringify(&roll) {
-        Ok(mut x) => {
-            if x.len() > serenity::constants::MESSAGE_CODE_LIMIT {
-                x = format!(
-                    "{}... = `{}`",
-                    &x[..serenity::constants::MESSAGE_CODE_LIMIT - 30],
-                    roll.total().unwrap()
+    let day = day_number(roll.at);
+    let mut embed = embed
+        .title(format!("Roll #{id}"))
+        .field(
+            "Rolled by",
+            serenity::UserId::new(roll.user).mention().to_string(),
+            true,
+        )
+        .field("At", format!("<t:{}:f>", roll.at.unix_timestamp()), true)
+        .field("Roll", roll.kind.to_string(), true)
+        .field("Dice", truncate(&dice_values(&roll.dice)), false);
+    match ctx
+        .data()
+        .use_fairness(|fairness| fairness.revealed_seed(day, now))
+    {
+        Some(seed) => {
+            let recomputed = roll.kind.roll(&mut seeded::rng(&seed, id));
+            let description = if recomputed.values() == roll.dice {
+                "✅ This roll matches the seed it was made with."
+            } else {
+                "⚠️ This roll doesn't match the seed it was made with."
+            };
+            embed = embed
+                .description(description)
+                .field("Seed", format!("`{}`", seeded::to_hex(&seed)), false)
+                .field(
+                    "Hash of the seed",
+                    format!("`{}`", seeded::to_hex(&seeded::commitment(&seed))),
+                    false,
+                )
+                .field(
+                    "Recomputed result",
+                    truncate(&recomputed.to_string()),
+                    false,
                 );
-            }
+        }
+        None => {
+            // The seed was created when the roll was made
+            let commitment = ctx
+                .data()
+                .use_fairness(|fairness| fairness.commitment(day))
+                .unwrap();
+            embed = embed.description(format!(
+                "This roll's seed will be revealed after midnight UTC. Until then, its hash is \
+                `{}`.",
+                seeded::to_hex(&commitment)
+            ));
+        }
+    }
 
-            ctx.send(
-                poise::CreateReply::default()
-                    .content(x)
-                    .ephemeral(private)
-                    .reply(true),
-            )
+    let reply = poise::CreateReply::default()
+        .embed(embed)
+        .ephemeral(roll.private)
+        .reply(true);
+    ctx.send(reply).await?;
+    Ok(())
+}
+
+fn dice_values(dice: &[i32]) -> String {
+    if dice.is_empty() {
+        return String::from("No dice");
+    }
+    let dice: Vec<_> = dice.iter().map(i32::to_string).collect();
+    dice.join(" ")
+}
+
+/// Rolls dice that only the channel's GM can see
+#[poise::command(slash_command, guild_only)]
+pub async fn secret(
+    ctx: ApplicationContext<'_>,
+    #[description = "The dice to roll"] dice: Option<String>,
+) -> Result {
+    let Some(game_master) = ctx.data().use_game_masters(|gms| gms.get(ctx.channel_id())) else {
+        reply_error!(
+            ctx,
+            "This channel doesn't have a GM. Choose one with `/dice gm`."
+        );
+    };
+
+    let dice = dice.unwrap_or_else(|| String::from("1d20"));
+    let dice = match expand_dice(ctx.into(), &dice) {
+        Ok(dice) => dice,
+        Err(err) => reply_error!(ctx, "Error: {}", err),
+    };
+    let expr: Expr = match dice.parse() {
+        Ok(expr) => expr,
+        Err(err) => reply_error!(ctx, "Couldn't read `{}`: {}.", dice, err),
+    };
+    let kind = FairRollKind::Dice {
+        expression: expr.to_string(),
+    };
+    let (id, Rolled::Dice(roll)) = secret_fair_roll(ctx.data(), ctx.author().id, kind, game_master)
+    else {
+        unreachable!()
+    };
+    let result = fit_message(format_roll(&roll), roll.total);
+    // Secret rolls aren't recorded, since `/dice stats` would give them away
+
+    let author_name = crate::author_name(ctx.into()).await;
+    let content =
+        format!("🎲 **{author_name}** secretly rolled `{expr}`:\n{result}\n-# Roll #{id}");
+    if let GameMaster::User(id) = game_master {
+        // The GM can still see the roll with the button if their DMs are closed
+        let dm = serenity::CreateMessage::new().content(&content);
+        let _ = serenity::UserId::new(id)
+            .direct_message(ctx.serenity_context(), dm)
+            .await;
+    }
+
+    let mut secret_roll = SecretRoll {
+        content,
+        game_master,
+    };
+    let button = serenity::CreateButton::new(format!("{}_secret_show", ctx.id()))
+        .emoji("👁️".parse::<serenity::ReactionType>().unwrap())
+        .label("Show the GM")
+        .style(serenity::ButtonStyle::Secondary);
+    // Members of a GM role each see the result when they press the button
+    let message = InteractiveMessage::new()
+        .content(format!(
+            "🎲 {author_name} made a secret roll. Only {} can see it.",
+            game_master.mention()
+        ))
+        .action_rows([serenity::CreateActionRow::Buttons(vec![button])]);
+    let config = InteractiveConfig {
+        duration: SECRET_ROLL_DURATION,
+        update_interval: Duration::ZERO,
+    };
+    secret_roll.run(ctx, config, message).await?;
+    Ok(())
+}
+
+/// A secret roll's public placeholder, whose button shows the result to the GM
+struct SecretRoll {
+    content: String,
+    game_master: GameMaster,
+}
+
+impl Interactive for SecretRoll {
+    const KIND: &'static str = "secret roll";
+
+    fn permission(&self, _custom_id: &str) -> Permission {
+        match self.game_master {
+            GameMaster::User(id) => Permission::User(serenity::UserId::new(id)),
+            GameMaster::Role(id) => Permission::Role(serenity::RoleId::new(id)),
+        }
+    }
+
+    async fn process(
+        &mut self,
+        _ctx: &SessionContext<'_>,
+        _interaction: &Interaction,
+    ) -> Result<ControlFlow> {
+        Ok(ControlFlow::ephemeral(self.content.clone()))
+    }
+
+    fn update(&mut self, _ctx: &SessionContext<'_>, _message: &mut InteractiveMessage) {}
+
+    async fn finish(
+        &mut self,
+        _ctx: &SessionContext<'_>,
+        message: &mut InteractiveMessage,
+        _reason: EndReason,
+    ) -> Result<()> {
+        message.modify_action_rows(|_| Vec::new());
+        Ok(())
+    }
+}
+
+pub(in crate::commands) async fn is_game_master(ctx: Context<'_>, game_master: GameMaster) -> bool {
+    match game_master {
+        GameMaster::User(id) => ctx.author().id.get() == id,
+        GameMaster::Role(id) => ctx
+            .author_member()
+            .await
+            .is_some_and(|member| member.roles.contains(&serenity::RoleId::new(id))),
+    }
+}
+
+/// Chooses who sees the secret rolls made in this channel. Leave both empty to clear the GM.
+#[poise::command(slash_command, guild_only, rename = "gm")]
+pub async fn set_gm(
+    ctx: Context<'_>,
+    #[description = "The GM"] user: Option<serenity::User>,
+    #[description = "A role whose members are all GMs"] role: Option<serenity::Role>,
+) -> Result {
+    let game_master = match (user, role) {
+        (Some(_), Some(_)) => reply_error!(ctx, "Choose either a user or a role, not both."),
+        (Some(user), None) => Some(GameMaster::User(user.id.get())),
+        (None, Some(role)) => Some(GameMaster::Role(role.id.get())),
+        (None, None) => None,
+    };
+
+    // Only the current GM can hand the job to someone else
+    let current = ctx.data().use_game_masters(|gms| gms.get(ctx.channel_id()));
+    let author_is_gm = match current {
+        None => true,
+        Some(game_master) => is_game_master(ctx, game_master).await,
+    };
+    let author_is_owner = ctx.framework().options().owners.contains(&ctx.author().id);
+    if !author_is_gm && !author_is_owner {
+        reply_error!(ctx, "Only the current GM can change who the GM is.");
+    }
+
+    ctx.data()
+        .use_game_masters_mut(|gms| gms.set(ctx.channel_id(), game_master));
+    let content = match game_master {
+        Some(game_master) => format!(
+            "Secret rolls in this channel will go to {}.",
+            game_master.mention()
+        ),
+        None => String::from("This channel no longer has a GM."),
+    };
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .allowed_mentions(serenity::CreateAllowedMentions::new())
+            .reply(true),
+    )
+    .await?;
+    Ok(())
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Reroll {
+    Same,
+    Advantage,
+    Disadvantage,
+    PlusOne,
+    MinusOne,
+    Crit,
+}
+
+impl Reroll {
+    const ALL: [Self; 6] = [
+        Self::Same,
+        Self::Advantage,
+        Self::Disadvantage,
+        Self::PlusOne,
+        Self::MinusOne,
+        Self::Crit,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Same => "same",
+            Self::Advantage => "adv",
+            Self::Disadvantage => "dis",
+            Self::PlusOne => "plus",
+            Self::MinusOne => "minus",
+            Self::Crit => "crit",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Same => "Reroll",
+            Self::Advantage => "Advantage",
+            Self::Disadvantage => "Disadvantage",
+            Self::PlusOne => "+1",
+            Self::MinusOne => "-1",
+            Self::Crit => "Crit (double dice)",
+        }
+    }
+
+    /// The expression to roll instead. Returns `None` if the change doesn't apply to it.
+    fn apply(self, expr: &Expr) -> Option<Expr> {
+        match self {
+            Self::Same => Some(expr.clone()),
+            Self::Advantage => expr.with_advantage(true),
+            Self::Disadvantage => expr.with_advantage(false),
+            Self::PlusOne => Some(expr.with_modifier(1)),
+            Self::MinusOne => Some(expr.with_modifier(-1)),
+            Self::Crit => expr.with_double_dice(),
+        }
+    }
+}
+
+/// Buttons on a roll's result for rolling the same dice again, with changes. The dice are kept in
+/// the buttons' custom IDs, so that [`reroll`] can handle them without a session. Returns `None`
+/// if the expression is too long to fit.
+fn reroll_buttons(expr: &Expr) -> Option<Vec<serenity::CreateActionRow>> {
+    let expression = expr.to_string();
+    let rows = Reroll::ALL
+        .chunks(3)
+        .map(|row| {
+            let buttons = row
+                .iter()
+                .map(|&reroll| {
+                    let custom_id = reroll_id(reroll, &expression);
+                    if custom_id.len() > MAX_CUSTOM_ID_LEN {
+                        return None;
+                    }
+                    let style = if reroll == Reroll::Same {
+                        serenity::ButtonStyle::Primary
+                    } else {
+                        serenity::ButtonStyle::Secondary
+                    };
+                    let button = serenity::CreateButton::new(custom_id)
+                        .label(reroll.label())
+                        .style(style)
+                        .disabled(reroll.apply(expr).is_none());
+                    Some(button)
+                })
+                .collect::<Option<_>>()?;
+            Some(serenity::CreateActionRow::Buttons(buttons))
+        })
+        .collect::<Option<_>>()?;
+    Some(rows)
+}
+
+fn reroll_id(reroll: Reroll, expression: &str) -> String {
+    format!("{REROLL_ID_PREFIX}{}_{expression}", reroll.name())
+}
+
+fn parse_reroll_id(custom_id: &str) -> Option<(Reroll, Expr)> {
+    let (name, expression) = custom_id.strip_prefix(REROLL_ID_PREFIX)?.split_once('_')?;
+    let reroll = Reroll::ALL
+        .into_iter()
+        .find(|reroll| reroll.name() == name)?;
+    Some((reroll, expression.parse().ok()?))
+}
+
+/// Rolls again when someone presses one of the buttons on a roll's result. Each new result is
+/// posted as a reply to the original.
+pub async fn reroll(ctx: &serenity::Context, event: &serenity::FullEvent, data: &Data) -> Result {
+    let serenity::FullEvent::InteractionCreate {
+        interaction: serenity::Interaction::Component(interaction),
+    } = event
+    else {
+        return Ok(());
+    };
+    let Some((reroll, expr)) = parse_reroll_id(&interaction.data.custom_id) else {
+        return Ok(());
+    };
+
+    let Some(expr) = reroll.apply(&expr) else {
+        let message = serenity::CreateInteractionResponseMessage::new()
+            .content(":x: That doesn't work with these dice.")
+            .ephemeral(true);
+        interaction
+            .create_response(ctx, serenity::CreateInteractionResponse::Message(message))
             .await?;
+        return Ok(());
+    };
+
+    let user_id = interaction.user.id;
+    let kind = FairRollKind::Dice {
+        expression: expr.to_string(),
+    };
+    let (id, Rolled::Dice(roll)) = fair_roll(data, user_id, kind, false) else {
+        unreachable!()
+    };
+    let record = RollRecord {
+        expression: expr.to_string(),
+        total: roll.total,
+        at: time::OffsetDateTime::now_utc(),
+        user: user_id.get(),
+        channel: interaction.channel_id.get(),
+    };
+    data.use_rolls_mut(|rolls| rolls.record(record));
+
+    let label = format!("**{}** `{expr}`", reroll.label());
+    let (result, image) = roll_result(&label, &roll, false);
+    let mut message = serenity::CreateInteractionResponseMessage::new()
+        .content(format!("{result}\n-# Roll #{id}"));
+    if let Some(image) = image {
+        message = message.add_file(image);
+    }
+    interaction
+        .create_response(ctx, serenity::CreateInteractionResponse::Message(message))
+        .await?;
+    Ok(())
+}
+
+/// Formats a roll like `[~~3~~, 17] + 5 = **22**`, striking out the dice that were dropped
+pub(in crate::commands) fn format_roll(roll: &Roll) -> String {
+    let mut formatted = String::new();
+    for (i, term) in roll.terms.iter().enumerate() {
+        match (i, term.negative) {
+            (0, true) => formatted.push('-'),
+            (0, false) => {}
+            (_, true) => formatted.push_str(" - "),
+            (_, false) => formatted.push_str(" + "),
         }
-        Err(err) => {
-            reply_error!(ctx, "Error: {}", err);
+        match &term.kind {
+            RolledKind::Constant(n) => write!(formatted, "{n}").unwrap(),
+            RolledKind::Dice { rolls, .. } => {
+                let rolls: Vec<_> = rolls
+                    .iter()
+                    .map(|roll| {
+                        if roll.kept {
+                            roll.value.to_string()
+                        } else {
+                            format!("~~{}~~", roll.value)
+                        }
+                    })
+                    .collect();
+                write!(formatted, "[{}]", rolls.join(", ")).unwrap();
+            }
         }
     }
+    write!(formatted, " = **{}**", roll.total).unwrap();
+    formatted
+}
+
+/// Expands the `@macros` in the expression, then fills in `$stats` from the author's current
+/// character
+fn expand_dice(ctx: Context<'_>, dice: &str) -> Result<String> {
+    expand_dice_for(ctx.data(), ctx.author().id, ctx.guild_id(), dice)
+}
+
+fn expand_dice_for(
+    data: &Data,
+    user_id: serenity::UserId,
+    guild_id: Option<serenity::GuildId>,
+    dice: &str,
+) -> Result<String> {
+    let dice = data.use_dice_macros(|macros| macros.expand(dice, user_id, guild_id))?;
+    let dice = data.use_characters(|characters| characters.substitute(user_id, &dice))?;
+    Ok(dice)
+}
+
+/// Rolls the `[[1d20+5]]` expressions in ordinary messages, replying with the results. Anything
+/// between `[[` and `]]` that isn't dice, like a wiki link, is ignored.
+pub async fn inline_rolls(
+    ctx: &serenity::Context,
+    event: &serenity::FullEvent,
+    data: &Data,
+) -> Result {
+    let serenity::FullEvent::Message {
+        new_message: message,
+    } = event
+    else {
+        return Ok(());
+    };
+    if message.author.bot {
+        return Ok(());
+    }
 
+    let lines: Vec<_> = inline_expressions(&message.content)
+        .filter_map(|dice| inline_roll(data, message, dice))
+        .take(MAX_INLINE_ROLLS)
+        .collect();
+    if lines.is_empty() {
+        return Ok(());
+    }
+    message.reply(ctx, lines.join("\n")).await?;
     Ok(())
 }
 
-#[poise::command(slash_command, rename = "wildsea")]
-pub async fn roll_wildsea(
+/// Finds the expressions between `[[` and `]]` in a message
+fn inline_expressions(content: &str) -> impl Iterator<Item = &str> {
+    content
+        .split("[[")
+        .skip(1)
+        .filter_map(|part| part.split_once("]]"))
+        .map(|(dice, _)| dice.trim())
+        .filter(|dice| !dice.is_empty())
+}
+
+/// Returns `None` if the expression isn't dice that Patbot's own engine understands
+fn inline_roll(data: &Data, message: &serenity::Message, dice: &str) -> Option<String> {
+    const MAX_LEN: usize = MAX_RESULT_LEN / MAX_INLINE_ROLLS;
+
+    let user_id = message.author.id;
+    let expr = expand_dice_for(data, user_id, message.guild_id, dice)
+        .and_then(|dice| Ok(dice.parse::<Expr>()?))
+        .ok()?;
+
+    let kind = FairRollKind::Dice {
+        expression: expr.to_string(),
+    };
+    let (id, Rolled::Dice(roll)) = fair_roll(data, user_id, kind, false) else {
+        unreachable!()
+    };
+    let record = RollRecord {
+        expression: expr.to_string(),
+        total: roll.total,
+        at: time::OffsetDateTime::now_utc(),
+        user: user_id.get(),
+        channel: message.channel_id.get(),
+    };
+    data.use_rolls_mut(|rolls| rolls.record(record));
+
+    let mut result = format!("`{expr}`: {}", format_roll(&roll));
+    if result.len() > MAX_LEN {
+        result = format!("`{expr}` = **{}**", roll.total);
+    }
+    Some(format!("{result} (#{id})"))
+}
+
+#[derive(poise::ChoiceParameter, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MacroScope {
+    #[name = "Just me"]
+    Personal,
+    #[name = "Everyone in this server"]
+    Server,
+}
+
+/// Returns `None` if the scope is the server, but the command wasn't used in one
+fn macro_owner(ctx: Context<'_>, scope: Option<MacroScope>) -> Option<MacroOwner> {
+    match scope.unwrap_or(MacroScope::Personal) {
+        MacroScope::Personal => Some(MacroOwner::User(ctx.author().id)),
+        MacroScope::Server => ctx.guild_id().map(MacroOwner::Guild),
+    }
+}
+
+/// Whether the author can change or delete the macro. Anyone can add a server macro, but only
+/// whoever saved it, server managers and the bot owners can change it after that.
+async fn can_change_macro(ctx: Context<'_>, owner: MacroOwner, name: &str) -> bool {
+    let MacroOwner::Guild(guild_id) = owner else {
+        return true;
+    };
+    let (exists, creator) = ctx
+        .data()
+        .use_dice_macros(|macros| (macros.contains(owner, name), macros.creator(guild_id, name)));
+    if !exists
+        || creator == Some(ctx.author().id)
+        || ctx.framework().options().owners.contains(&ctx.author().id)
+    {
+        return true;
+    }
+    ctx.author_member()
+        .await
+        .and_then(|member| member.permissions)
+        .is_some_and(|permissions| permissions.manage_guild())
+}
+
+/// Save dice to roll by name, or to use as `@name` in other rolls
+#[poise::command(slash_command, rename = "save")]
+pub async fn save_macro(
     ctx: Context<'_>,
-    #[description = "The dice to roll. Use `X cut Y` format, or `X` if no cuts."]
-    dice: String,
+    #[description = "The name of the macro"] name: String,
+    #[description = "The dice to roll, or a number such as a modifier"] dice: String,
+    #[description = "Who can use the macro. Defaults to just you"] scope: Option<MacroScope>,
 ) -> Result {
-    let Ok(dice) = dice.parse::<WildseaRoll>() else {
-        reply_error!(ctx, "Error: please use the format `X cut Y`, or `X` if no cuts.");
-    };
-    let (dice, cut) = dice.into_parts();
-    
-    if cut >= dice {
-        reply_error!(ctx, "Error: the number of dice to cut must be less than the number of dice to roll.");
-    }
-    
-    let mut all_rolls: Vec<_> = {
-        let mut rng = rand::rng();
-        std::iter::repeat_with(|| rng.random_range(1_u32..=6_u32))
-            .take(dice as usize)
+    let name = name.trim_start_matches('@').to_ascii_lowercase();
+    if name.is_empty() || !name.chars().all(is_name_char) {
+        reply_error!(
+            ctx,
+            "Macro names can only contain letters, numbers and underscores."
+        );
+    }
+    let Some(owner) = macro_owner(ctx, scope) else {
+        reply_error!(ctx, "Server macros can only be saved in a server.");
+    };
+    if !can_change_macro(ctx, owner, &name).await {
+        reply_error!(
+            ctx,
+            "Only whoever saved `@{}` or a server manager can change it.",
+            name
+        );
+    }
+
+    let saved = ctx.data().use_dice_macros_mut(|macros| {
+        macros.save(
+            owner,
+            name.clone(),
+            dice.clone(),
+            ctx.author().id,
+            ctx.guild_id(),
+        )
+    });
+    match saved {
+        Ok(Some(previous)) => {
+            ctx.reply(format!("Changed `@{name}` from `{previous}` to `{dice}`."))
+                .await?;
+        }
+        Ok(None) => {
+            ctx.reply(format!("Saved `@{name}` as `{dice}`.")).await?;
+        }
+        Err(err) => {
+            reply_error!(ctx, "Couldn't save `@{}`: {}.", name, err);
+        }
+    }
+    Ok(())
+}
+
+/// Roll a saved macro
+#[poise::command(slash_command, rename = "macro")]
+pub async fn roll_macro(
+    ctx: Context<'_>,
+    #[description = "The name of the macro"]
+    #[autocomplete = "autocomplete_macro"]
+    name: String,
+    #[description = "The roll will be visible only to you"]
+    #[flag]
+    private: bool,
+) -> Result {
+    let name = name.trim_start_matches('@');
+    roll_inner(ctx, Some(format!("@{name}")), private, false).await
+}
+
+/// List the macros you can use
+#[poise::command(slash_command, rename = "macros")]
+pub async fn list_macros(ctx: ApplicationContext<'_>) -> Result {
+    const MACROS_PER_PAGE: usize = 15;
+
+    let user_id = ctx.author().id;
+    let guild_id = ctx.guild_id();
+    let lines: Vec<_> = ctx.data().use_dice_macros(|macros| {
+        let personal = macros
+            .list(MacroOwner::User(user_id))
+            .map(|(name, dice)| format!("`@{name}`: `{dice}`"));
+        let server = guild_id
+            .into_iter()
+            .flat_map(|guild_id| macros.list(MacroOwner::Guild(guild_id)))
+            .map(|(name, dice)| format!("`@{name}`: `{dice}` (server)"));
+        personal.chain(server).collect()
+    });
+    Paginator::from_lines(ctx, "Dice Macros", lines, MACROS_PER_PAGE)
+        .send(ctx)
+        .await
+}
+
+/// Delete a saved macro
+#[poise::command(slash_command, rename = "delete")]
+pub async fn delete_macro(
+    ctx: Context<'_>,
+    #[description = "The name of the macro"]
+    #[autocomplete = "autocomplete_macro"]
+    name: String,
+    #[description = "Whose macro to delete. Defaults to yours"] scope: Option<MacroScope>,
+) -> Result {
+    let name = name.trim_start_matches('@').to_ascii_lowercase();
+    let Some(owner) = macro_owner(ctx, scope) else {
+        reply_error!(ctx, "Server macros can only be deleted in a server.");
+    };
+    if !can_change_macro(ctx, owner, &name).await {
+        reply_error!(
+            ctx,
+            "Only whoever saved `@{}` or a server manager can delete it.",
+            name
+        );
+    }
+    let was_deleted = ctx
+        .data()
+        .use_dice_macros_mut(|macros| macros.delete(owner, &name));
+    if was_deleted {
+        ctx.reply(format!("Deleted `@{name}`.")).await?;
+    } else {
+        reply_error!(ctx, "That macro does not exist.");
+    }
+    Ok(())
+}
+
+async fn autocomplete_macro<'a>(
+    ctx: Context<'_>,
+    partial: &'a str,
+) -> impl Iterator<Item = String> + 'a {
+    let partial = partial.trim_start_matches('@');
+    let names: Vec<_> = ctx.data().use_dice_macros(|macros| {
+        macros
+            .names(ctx.author().id, ctx.guild_id())
+            .into_iter()
+            .map(str::to_owned)
             .collect()
+    });
+    names
+        .into_iter()
+        .filter(move |name| name.starts_with(partial))
+}
+
+/// Show the odds of every result of a dice expression
+#[poise::command(slash_command)]
+pub async fn odds(
+    ctx: Context<'_>,
+    #[description = "The dice to roll, like `2d20kh1+5`"] dice: String,
+    #[description = "Show the chance of rolling at least this"] target: Option<i64>,
+) -> Result {
+    let dice = match expand_dice(ctx, &dice) {
+        Ok(dice) => dice,
+        Err(err) => reply_error!(ctx, "Error: {}", err),
+    };
+    let expr: Expr = match dice.parse() {
+        Ok(expr) => expr,
+        Err(err) => reply_error!(ctx, "Couldn't read `{}`: {}.", dice, err),
+    };
+    let distribution = tokio::task::spawn_blocking({
+        let expr = expr.clone();
+        move || expr.distribution()
+    });
+    let Some(distribution) = distribution.await? else {
+        reply_error!(
+            ctx,
+            "`{}` has too many possible results to work out the odds exactly.",
+            expr
+        );
     };
-    all_rolls.sort_unstable();
 
-    let rolls = &all_rolls[..all_rolls.len() - cut as usize];
-    let highest_roll = rolls[rolls.len() - 1];
-    let has_doubles = rolls
-        .windows(2)
-        .any(|w| w[0] == w[1]);
-    
-    let has_doubles = if has_doubles {
-        "... with a twist"
+    let mut fields = vec![
+        (
+            String::from("Average"),
+            format!("{:.2}", distribution.mean()),
+            true,
+        ),
+        (
+            String::from("Standard Deviation"),
+            format!("{:.2}", distribution.std_dev()),
+            true,
+        ),
+        (
+            String::from("Range"),
+            format!("{} to {}", distribution.min(), distribution.max()),
+            true,
+        ),
+    ];
+    if let Some(target) = target {
+        fields.push((
+            format!("Chance of {target} or Higher"),
+            format_probability(distribution.at_least(target)),
+            false,
+        ));
+    }
+
+    let embed = serenity::CreateEmbed::default()
+        .color(ctx.data().bot_color())
+        .title(format!("🎲 Odds for {expr}"))
+        .description(format!("```\n{}\n```", histogram(&distribution)))
+        .fields(fields);
+    ctx.send(poise::CreateReply::default().embed(embed).reply(true))
+        .await?;
+    Ok(())
+}
+
+fn format_probability(p: f64) -> String {
+    if p > 0. && p < 0.0001 {
+        String::from("<0.01%")
     } else {
-        ""
+        format!("{:.2}%", p * 100.)
+    }
+}
+
+/// Draws a bar for each result. Results are grouped together if there are too many to fit.
+fn histogram(distribution: &Distribution) -> String {
+    const MAX_ROWS: usize = 20;
+    const BAR_WIDTH: usize = 20;
+
+    let results: Vec<_> = distribution.iter().collect();
+    let group_size = results.len().div_ceil(MAX_ROWS);
+    let groups: Vec<_> = results
+        .chunks(group_size)
+        .map(|group| {
+            let (first, _) = group[0];
+            let (last, _) = group[group.len() - 1];
+            let label = if first == last {
+                first.to_string()
+            } else {
+                format!("{first}-{last}")
+            };
+            let p: f64 = group.iter().map(|&(_, p)| p).sum();
+            (label, p)
+        })
+        .collect();
+
+    let label_width = groups
+        .iter()
+        .map(|(label, _)| label.len())
+        .max()
+        .unwrap_or(0);
+    let highest = groups.iter().map(|&(_, p)| p).fold(0., f64::max);
+    let lines: Vec<_> = groups
+        .iter()
+        .map(|(label, p)| {
+            let bar = "█".repeat((p / highest * BAR_WIDTH as f64).round() as usize);
+            format!(
+                "{label:>label_width$} │{bar:<BAR_WIDTH$} {:>6.2}%",
+                p * 100.
+            )
+        })
+        .collect();
+    lines.join("\n")
+}
+
+/// Show someone's dice statistics, and who the luckiest and unluckiest rollers are
+#[poise::command(slash_command)]
+pub async fn stats(
+    ctx: Context<'_>,
+    #[description = "Whose statistics to show. Defaults to you"] member: Option<serenity::User>,
+) -> Result {
+    /// Members need this many rolls with a known distribution to be ranked by luck
+    const MIN_ROLLS_FOR_RANKING: u32 = 10;
+
+    let user = member.as_ref().unwrap_or_else(|| ctx.author());
+    let records: Vec<_> = ctx
+        .data()
+        .use_rolls(|rolls| rolls.iter().cloned().collect());
+    let all_stats = tokio::task::spawn_blocking(move || RollStats::by_user(&records)).await?;
+    let Some(stats) = all_stats.get(&user.id) else {
+        reply_error!(ctx, "{} hasn't rolled any dice yet.", user.name);
     };
-    let message = match highest_roll {
-        1..=3 => format!("Disaster{has_doubles}!"),
-        4..=5 => format!("Conflict{has_doubles}!"),
-        6 => format!("Triumph{has_doubles}!"),
-        _ => unreachable!(),
+
+    let percent = |n: u32, of: u32| match of {
+        0 => String::from("no d20 rolls"),
+        _ => format!("{:.1}% of d20 rolls", f64::from(n) * 100. / f64::from(of)),
     };
-    
-    let rolls = all_rolls
+    let mut fields = vec![
+        (String::from("Rolls"), stats.rolls.to_string(), true),
+        (
+            String::from("Natural 20s"),
+            format!(
+                "{} ({})",
+                stats.nat20s,
+                percent(stats.nat20s, stats.d20_rolls)
+            ),
+            true,
+        ),
+        (
+            String::from("Natural 1s"),
+            format!(
+                "{} ({})",
+                stats.nat1s,
+                percent(stats.nat1s, stats.d20_rolls)
+            ),
+            true,
+        ),
+    ];
+    if let (Some(difference), Some(luck)) = (stats.average_difference(), stats.luck()) {
+        fields.push((
+            String::from("Compared to Expected"),
+            format!("{difference:+.2} per roll on average"),
+            true,
+        ));
+        fields.push((
+            String::from("Luck"),
+            format!("{:.0}% (50% is average)", luck * 100.),
+            true,
+        ));
+    }
+
+    let mut ranked: Vec<_> = all_stats
         .iter()
-        .enumerate()
-        .fold(String::new(), |acc, (i, roll)| {
-            if i >= rolls.len() {
-                format!("{acc},~~{roll}~~")
-            } else if acc.is_empty() {
-                roll.to_string()
-            } else {
-                format!("{acc},{roll}")
-            }
-        });
-    
-    ctx.send(
-        poise::CreateReply::default()
-            .content(format!("Rolls: {rolls}\n{message}"))
-            .reply(true)
-    ).await?;
-    
+        .filter(|(_, stats)| stats.scored_rolls >= MIN_ROLLS_FOR_RANKING)
+        .filter_map(|(&user_id, stats)| Some((user_id, stats.luck()?)))
+        .collect();
+    ranked.sort_unstable_by(|(_, a), (_, b)| b.total_cmp(a));
+    if let [(luckiest, best), .., (unluckiest, worst)] = ranked[..] {
+        let ranking = |user_id: serenity::UserId, luck: f64| {
+            format!("{} ({:.0}%)", user_id.mention(), luck * 100.)
+        };
+        fields.push((String::from("Luckiest"), ranking(luckiest, best), false));
+        fields.push((
+            String::from("Unluckiest"),
+            ranking(unluckiest, worst),
+            false,
+        ));
+    }
+
+    let embed = serenity::CreateEmbed::default()
+        .color(ctx.data().bot_color())
+        .title("🎲 Dice Statistics")
+        .description(format!("Statistics for {}", user.mention()))
+        .fields(fields);
+    ctx.send(poise::CreateReply::default().embed(embed).reply(true))
+        .await?;
     Ok(())
 }
 
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct RollRecord {
+    pub expression: String,
+    pub total: i64,
+    pub at: time::OffsetDateTime,
+    pub user: u64,
+    pub channel: u64,
+}
+
+#[derive(Default)]
+struct RollStats {
+    rolls: u32,
+    d20_rolls: u32,
+    nat20s: u32,
+    nat1s: u32,
+    /// Rolls of expressions whose distribution is known, which are the only ones compared
+    /// against their expected values
+    scored_rolls: u32,
+    total_difference: f64,
+    total_percentile: f64,
+}
+
+impl RollStats {
+    /// Roughly how many steps working out the distributions of every recorded expression may
+    /// take. Rolls of expressions that don't fit aren't scored.
+    const MAX_WORK: u64 = 200_000_000;
+
+    fn by_user<'a>(
+        records: impl IntoIterator<Item = &'a RollRecord>,
+    ) -> HashMap<serenity::UserId, Self> {
+        let mut budget = Budget::new(Self::MAX_WORK);
+        let mut expressions: HashMap<&str, Option<(Expr, Option<Distribution>)>> = HashMap::new();
+        let mut by_user: HashMap<_, Self> = HashMap::new();
+        for record in records {
+            let stats = by_user
+                .entry(serenity::UserId::new(record.user))
+                .or_default();
+            stats.rolls += 1;
+
+            // Expressions that Patbot's dice engine doesn't understand can't be analyzed
+            let expression = expressions
+                .entry(record.expression.as_str())
+                .or_insert_with(|| {
+                    let expr: Expr = record.expression.parse().ok()?;
+                    let distribution = expr.distribution_within(&mut budget);
+                    Some((expr, distribution))
+                });
+            let Some((expr, distribution)) = expression else {
+                continue;
+            };
+
+            if let Some(natural) = expr.natural_d20(record.total) {
+                stats.d20_rolls += 1;
+                match natural {
+                    20 => stats.nat20s += 1,
+                    1 => stats.nat1s += 1,
+                    _ => {}
+                }
+            }
+            if let Some(distribution) = distribution {
+                stats.scored_rolls += 1;
+                stats.total_difference += record.total as f64 - distribution.mean();
+                stats.total_percentile += distribution.percentile(record.total);
+            }
+        }
+        by_user
+    }
+
+    fn average_difference(&self) -> Option<f64> {
+        (self.scored_rolls > 0).then(|| self.total_difference / f64::from(self.scored_rolls))
+    }
+
+    /// The average percentile of this member's rolls
+    fn luck(&self) -> Option<f64> {
+        (self.scored_rolls > 0).then(|| self.total_percentile / f64::from(self.scored_rolls))
+    }
+}
+
+#[test]
+fn test_dice_systems() {
+    assert_eq!(Blades { dice: 3 }.read(&[6, 2, 6]).tier, Tier::Critical);
+    assert_eq!(Blades { dice: 0 }.read(&[6, 6]).tier, Tier::Success);
+    assert_eq!(Blades { dice: 0 }.read(&[6, 3]).tier, Tier::Failure);
+    assert_eq!(Pbta { stat: 1 }.read(&[4, 5]).tier, Tier::Success);
+    assert_eq!(Pbta { stat: -1 }.read(&[4, 4]).tier, Tier::Partial);
+
+    let fate = Fate {
+        skill: 2,
+        opposition: 1,
+    };
+    assert_eq!(fate.read(&[1, 1, 0, -1]).title, "Success: Good (+3)");
+    assert_eq!(fate.read(&[-1, -1, 0, -1]).tier, Tier::Failure);
+
+    let wod = Wod {
+        dice: 4,
+        difficulty: 6,
+    };
+    assert_eq!(wod.read(&[1, 3, 2, 5]).title, "Botch!");
+    assert_eq!(wod.read(&[6, 10, 1, 7]).title, "2 successes");
+    assert_eq!(wod.read(&[6, 1, 2, 3]).title, "Failure");
+}
+
+#[tokio::test]
+async fn test_secret_roll_role_gm() {
+    use crate::interactive::{
+        fake::{self, FakeGateway},
+        Response,
+    };
+
+    let data = fake::data();
+    let ctx = fake::session_context(&data, 1);
+    let content = String::from("🎲 **member1** secretly rolled `1d20`: [7] = **7**");
+    let mut secret_roll = SecretRoll {
+        content: content.clone(),
+        game_master: GameMaster::Role(50),
+    };
+
+    let mut gateway = FakeGateway::new();
+    gateway
+        .click(1, "1_secret_show")
+        .click_with_roles(2, &[40, 50], "1_secret_show");
+
+    let message = InteractiveMessage::new().content("🎲 member1 made a secret roll.");
+    let config = InteractiveConfig {
+        duration: Duration::from_secs(60),
+        update_interval: Duration::ZERO,
+    };
+    message
+        .run_with(&ctx, &mut gateway, config, &mut secret_roll)
+        .await
+        .unwrap();
+
+    // The roller is turned away, and a member with the GM role sees the result
+    assert_eq!(gateway.ephemerals(), 2);
+    let (user_id, Response::Ephemeral(shown)) = &gateway.responses[1] else {
+        panic!("the GM wasn't shown the roll");
+    };
+    assert_eq!(user_id.get(), 2);
+    assert_eq!(shown.content.as_ref(), Some(&content));
+}
+
+#[test]
+fn test_reroll_ids() {
+    let (reroll, expr) = parse_reroll_id(&reroll_id(Reroll::Advantage, "1d20+5")).unwrap();
+    assert_eq!(reroll, Reroll::Advantage);
+    assert_eq!(expr.to_string(), "1d20+5");
+    assert!(parse_reroll_id("reroll_nope_1d20").is_none());
+    assert!(parse_reroll_id("12345_page_main_next").is_none());
+}
+
+#[test]
+fn test_inline_expressions() {
+    let content = "I swing [[1d20+5]] for [[ 2d6 ]] damage, [[]] and [[oops";
+    let expressions: Vec<_> = inline_expressions(content).collect();
+    assert_eq!(expressions, ["1d20+5", "2d6"]);
+}
+
 #[test]
-fn test_parse_wildsea_dice() {
-    assert_eq!("3d6".parse::<WildseaRoll>().unwrap(), WildseaRoll::NoCut { dice: 3 });
-    assert_eq!("3d6 cut 1".parse::<WildseaRoll>().unwrap(), WildseaRoll::Cut { dice: 3, cut: 1 });   
+fn test_split_flags() {
+    assert_eq!(split_flags("1d20 + 5"), ("1d20 + 5", false, false));
+    assert_eq!(split_flags("2d6 private"), ("2d6", true, false));
+    assert_eq!(split_flags("8d6  image private "), ("8d6", true, true));
+    assert_eq!(split_flags("private"), ("", true, false));
+    assert_eq!(
+        split_flags("1d20 imageprivate"),
+        ("1d20 imageprivate", false, false)
+    );
 }
@@ -1,11 +1,11 @@
-use crate::prelude::*;
+use crate::{interactive::Paginator, prelude::*};
 
 type Command = poise::Command<crate::Data, crate::Error>;
 
 #[poise::command(
     slash_command,
     subcommand_required,
-    subcommands("create", "delete"),
+    subcommands("create", "delete", "list"),
     rename = "command"
 )]
 pub async fn reply(_ctx: ApplicationContext<'_>) -> Result {
@@ -141,6 +141,32 @@ pub async fn delete(
     Ok(())
 }
 
+/// List every Patbot command that has been created
+#[poise::command(slash_command)]
+pub async fn list(ctx: ApplicationContext<'_>) -> Result {
+    const COMMANDS_PER_PAGE: usize = 10;
+
+    let mut commands: Vec<_> = ctx.data().use_reply_commands(|commands| {
+        commands
+            .iter()
+            .map(|cmd| (cmd.name.clone(), cmd.description.clone(), cmd.owner))
+            .collect()
+    });
+    commands.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+    let lines = commands.into_iter().map(|(name, description, owner)| {
+        let owner = serenity::UserId::new(owner);
+        let owner_name = crate::get_frodge_member(owner).unwrap_or("unknown");
+        match description {
+            Some(description) => format!("`/{name}`: {description} (by {owner_name})"),
+            None => format!("`/{name}` (by {owner_name})"),
+        }
+    });
+    Paginator::from_lines(ctx, "Patbot Commands", lines, COMMANDS_PER_PAGE)
+        .send(ctx)
+        .await
+}
+
 /// Filters commands to only show ones that the author of the interaction can delete, then
 /// autocompletes based on the text they have entered
 async fn autocomplete_delete_param_command<'a>(
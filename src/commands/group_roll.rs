@@ -0,0 +1,461 @@
+use super::dice::{fair_roll, format_roll, FairRollKind, RollRecord, Rolled};
+use crate::{
+    commands::poll,
+    data::Data,
+    dice::{Expr, Roll, RolledKind},
+    interactive::{
+        Config as InteractiveConfig, ControlFlow, EndReason, Interaction, Interactive,
+        InteractiveMessage, Permission, SessionContext,
+    },
+    prelude::*,
+};
+use serenity::Mentionable;
+use std::time::Duration;
+
+const GROUP_ROLL_DURATION: Duration = Duration::from_secs(60 * 15);
+/// Keeps the list of rolls well under Discord's limit on embed descriptions
+const MAX_SIDES: usize = 20;
+
+#[derive(poise::ChoiceParameter, Default, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PoolResolve {
+    #[default]
+    #[name = "Highest die"]
+    Highest,
+    #[name = "Total of every roll"]
+    Total,
+}
+
+/// Starts a shared pool that everyone can add their dice to
+#[poise::command(slash_command, guild_only)]
+pub async fn pool(
+    ctx: ApplicationContext<'_>,
+    #[description = "The dice each person adds. Defaults to 1d6"] dice: Option<String>,
+    #[description = "How the pool is read. Defaults to the highest die"] resolve: Option<
+        PoolResolve,
+    >,
+    #[description = "Resolve the pool as soon as this many people have rolled"]
+    #[min = 1]
+    #[max = 20]
+    players: Option<u32>,
+    #[description = "What the roll is for"] title: Option<String>,
+) -> Result {
+    let dice = dice.unwrap_or_else(|| String::from("1d6"));
+    if let Err(err) = check_dice(&dice) {
+        reply_error!(ctx, "Error: {}", err);
+    }
+
+    let group = GroupRoll {
+        title: title.unwrap_or_else(|| String::from("Dice pool")),
+        embed_template: serenity::CreateEmbed::default(),
+        kind: GroupKind::Pool {
+            dice,
+            resolve: resolve.unwrap_or_default(),
+        },
+        sides: Vec::new(),
+        expected: players.map(|players| players as usize),
+    };
+    group.start(ctx).await
+}
+
+/// Challenges someone to a contested roll. You each roll your own side and the higher total wins.
+#[poise::command(slash_command, guild_only)]
+pub async fn contest(
+    ctx: ApplicationContext<'_>,
+    #[description = "Who you're rolling against"] opponent: serenity::User,
+    #[description = "The dice you roll. Defaults to 1d20"] dice: Option<String>,
+    #[description = "The dice they roll. Defaults to the same as yours"] opponent_dice: Option<
+        String,
+    >,
+    #[description = "What the contest is for"] title: Option<String>,
+) -> Result {
+    if opponent.id == ctx.author().id || opponent.bot {
+        reply_error!(ctx, "You can't contest a roll against yourself or a bot.");
+    }
+    let dice = dice.unwrap_or_else(|| String::from("1d20"));
+    let opponent_dice = opponent_dice.unwrap_or_else(|| dice.clone());
+    for dice in [&dice, &opponent_dice] {
+        if let Err(err) = check_dice(dice) {
+            reply_error!(ctx, "Error: {}", err);
+        }
+    }
+
+    let side = |user, dice| Side {
+        user,
+        dice,
+        rolled: None,
+    };
+    let group = GroupRoll {
+        title: title.unwrap_or_else(|| String::from("Contest")),
+        embed_template: serenity::CreateEmbed::default(),
+        kind: GroupKind::Contest,
+        sides: vec![
+            side(ctx.author().id, dice),
+            side(opponent.id, opponent_dice),
+        ],
+        expected: Some(2),
+    };
+    group.start(ctx).await
+}
+
+/// Checks that dice can be rolled. Dice with `$stats` are checked when each person rolls them,
+/// since they're filled in from that person's character.
+fn check_dice(dice: &str) -> Result {
+    if !dice.contains('$') {
+        dice.parse::<Expr>()?;
+    }
+    Ok(())
+}
+
+/// Fills in the roller's `$stats` and parses the dice
+fn parse_dice(data: &Data, user: serenity::UserId, dice: &str) -> Result<Expr> {
+    let dice = data.use_characters(|characters| characters.substitute(user, dice))?;
+    Ok(dice.parse()?)
+}
+
+enum GroupKind {
+    /// Anyone can join by rolling the same dice
+    Pool { dice: String, resolve: PoolResolve },
+    /// Only the two sides can roll, and the higher total wins
+    Contest,
+}
+
+struct Side {
+    user: serenity::UserId,
+    dice: String,
+    /// The roll's ID and result
+    rolled: Option<(u64, Roll)>,
+}
+
+struct GroupRoll {
+    title: String,
+    embed_template: serenity::CreateEmbed,
+    kind: GroupKind,
+    /// In the order they joined
+    sides: Vec<Side>,
+    /// The group is resolved as soon as this many people have rolled
+    expected: Option<usize>,
+}
+
+impl GroupRoll {
+    async fn start(mut self, ctx: ApplicationContext<'_>) -> Result {
+        let author = poll::embed_author(ctx).await;
+        self.embed_template = serenity::CreateEmbed::default()
+            .color(ctx.data().bot_color())
+            .author(author);
+
+        let message = InteractiveMessage::new()
+            .embed(self.embed())
+            .action_rows(self.action_rows(ctx.id()));
+        let config = InteractiveConfig {
+            duration: GROUP_ROLL_DURATION,
+            update_interval: Duration::from_secs(1),
+        };
+        self.run(ctx, config, message).await?;
+        Ok(())
+    }
+
+    fn rolled(&self) -> impl Iterator<Item = (&Side, u64, &Roll)> {
+        self.sides
+            .iter()
+            .filter_map(|side| side.rolled.as_ref().map(|(id, roll)| (side, *id, roll)))
+    }
+
+    fn is_complete(&self) -> bool {
+        self.expected
+            .is_some_and(|expected| self.rolled().count() >= expected)
+    }
+
+    /// Rolls for the user, adding them to the pool if they aren't in it yet. Returns a message
+    /// for the user if they can't roll.
+    fn roll(&mut self, data: &Data, user: serenity::UserId, channel: u64) -> Result<(), String> {
+        let index = match self.sides.iter().position(|side| side.user == user) {
+            Some(index) => index,
+            None => match &self.kind {
+                GroupKind::Pool { .. } if self.sides.len() >= MAX_SIDES => {
+                    return Err(String::from(":x: This pool is full."));
+                }
+                GroupKind::Pool { dice, .. } => {
+                    self.sides.push(Side {
+                        user,
+                        dice: dice.clone(),
+                        rolled: None,
+                    });
+                    self.sides.len() - 1
+                }
+                GroupKind::Contest => {
+                    return Err(String::from(":x: You're not part of this contest."));
+                }
+            },
+        };
+        let side = &mut self.sides[index];
+        if side.rolled.is_some() {
+            return Err(String::from(":x: You've already rolled."));
+        }
+        let expr = match parse_dice(data, user, &side.dice) {
+            Ok(expr) => expr,
+            Err(err) => {
+                // Don't keep someone in the pool if they couldn't roll
+                if matches!(self.kind, GroupKind::Pool { .. }) {
+                    self.sides.remove(index);
+                }
+                return Err(format!(":x: Error: {err}"));
+            }
+        };
+
+        let kind = FairRollKind::Dice {
+            expression: expr.to_string(),
+        };
+        let (id, Rolled::Dice(roll)) = fair_roll(data, user, kind, false) else {
+            unreachable!()
+        };
+        let record = RollRecord {
+            expression: expr.to_string(),
+            total: roll.total,
+            at: time::OffsetDateTime::now_utc(),
+            user: user.get(),
+            channel,
+        };
+        data.use_rolls_mut(|rolls| rolls.record(record));
+        side.dice = expr.to_string();
+        side.rolled = Some((id, roll));
+        Ok(())
+    }
+
+    /// Sums up the rolls so far, like `Highest die: **5**`
+    fn result(&self) -> String {
+        match &self.kind {
+            GroupKind::Pool { .. } if self.rolled().next().is_none() => {
+                String::from("Nobody rolled.")
+            }
+            GroupKind::Pool {
+                resolve: PoolResolve::Highest,
+                ..
+            } => {
+                let (side, highest) = self
+                    .rolled()
+                    .filter_map(|(side, _, roll)| highest_die(roll).map(|die| (side, die)))
+                    .max_by_key(|&(_, die)| die)
+                    .unzip();
+                match highest {
+                    Some(highest) => format!(
+                        "Highest die: **{highest}**, rolled by {}",
+                        side.unwrap().user.mention()
+                    ),
+                    None => String::from("Nobody rolled any dice."),
+                }
+            }
+            GroupKind::Pool {
+                resolve: PoolResolve::Total,
+                ..
+            } => {
+                let total: i64 = self.rolled().map(|(_, _, roll)| roll.total).sum();
+                format!("Total: **{total}**")
+            }
+            GroupKind::Contest => {
+                let [a, b] = &self.sides[..] else {
+                    unreachable!()
+                };
+                match (&a.rolled, &b.rolled) {
+                    (Some((_, roll_a)), Some((_, roll_b))) => {
+                        match roll_a.total.cmp(&roll_b.total) {
+                            std::cmp::Ordering::Greater => {
+                                format!("{} wins!", a.user.mention())
+                            }
+                            std::cmp::Ordering::Less => format!("{} wins!", b.user.mention()),
+                            std::cmp::Ordering::Equal => String::from("It's a tie!"),
+                        }
+                    }
+                    (Some(_), None) => format!(
+                        "{} wins, since {} didn't roll.",
+                        a.user.mention(),
+                        b.user.mention()
+                    ),
+                    (None, Some(_)) => format!(
+                        "{} wins, since {} didn't roll.",
+                        b.user.mention(),
+                        a.user.mention()
+                    ),
+                    (None, None) => String::from("Nobody rolled."),
+                }
+            }
+        }
+    }
+
+    /// A line for each person, with their roll if they've rolled
+    fn lines(&self) -> Vec<String> {
+        const MAX_ROLL_LEN: usize = 150;
+
+        self.sides
+            .iter()
+            .map(|side| match &side.rolled {
+                Some((id, roll)) => {
+                    let mut result = format_roll(roll);
+                    if result.len() > MAX_ROLL_LEN {
+                        result = format!("**{}**", roll.total);
+                    }
+                    format!("{} `{}`: {result} (#{id})", side.user.mention(), side.dice)
+                }
+                None => format!("{} `{}`: ⏳", side.user.mention(), side.dice),
+            })
+            .collect()
+    }
+
+    fn embed(&self) -> serenity::CreateEmbed {
+        let mut lines = self.lines();
+        if let GroupKind::Pool { dice, .. } = &self.kind {
+            if self.sides.is_empty() {
+                lines.push(format!("Click **Roll** to add `{dice}` to the pool."));
+            }
+        }
+
+        let mut embed = self
+            .embed_template
+            .clone()
+            .title(self.title.clone())
+            .description(lines.join("\n"));
+        if let Some(expected) = self.expected {
+            let footer = format!("{}/{expected} rolled", self.rolled().count());
+            embed = embed.footer(serenity::CreateEmbedFooter::new(footer));
+        }
+        embed
+    }
+
+    fn action_rows(&self, id: u64) -> Vec<serenity::CreateActionRow> {
+        let roll = serenity::CreateButton::new(format!("{id}_group_roll"))
+            .label("Roll")
+            .style(serenity::ButtonStyle::Primary);
+        let resolve = serenity::CreateButton::new(format!("{id}_group_resolve"))
+            .label("Resolve")
+            .style(serenity::ButtonStyle::Secondary);
+        vec![serenity::CreateActionRow::Buttons(vec![roll, resolve])]
+    }
+}
+
+impl Interactive for GroupRoll {
+    const KIND: &'static str = "group roll";
+
+    fn permission(&self, custom_id: &str) -> Permission {
+        if custom_id.ends_with("_resolve") {
+            Permission::Author
+        } else {
+            Permission::Everyone
+        }
+    }
+
+    async fn process(
+        &mut self,
+        ctx: &SessionContext<'_>,
+        interaction: &Interaction,
+    ) -> Result<ControlFlow> {
+        let (_, name) = interaction.custom_id.rsplit_once('_').unwrap();
+        match name {
+            "roll" => {
+                let channel = ctx.channel_id().get();
+                if let Err(message) = self.roll(ctx.data(), interaction.user_id, channel) {
+                    return Ok(ControlFlow::ephemeral(message));
+                }
+                if self.is_complete() {
+                    return Ok(ControlFlow::Break);
+                }
+            }
+            "resolve" => return Ok(ControlFlow::Break),
+            _ => {}
+        }
+        Ok(ControlFlow::Continue { update: true })
+    }
+
+    fn update(&mut self, _ctx: &SessionContext<'_>, message: &mut InteractiveMessage) {
+        let embed = self.embed();
+        message.modify_embed(|_| embed);
+    }
+
+    async fn finish(
+        &mut self,
+        _ctx: &SessionContext<'_>,
+        message: &mut InteractiveMessage,
+        reason: EndReason,
+    ) -> Result<()> {
+        let embed = self.embed().title(format!("[Resolved] {}", self.title));
+        let description = format!("{}\n\n{}", self.result(), self.lines().join("\n"));
+        message
+            .modify_embed(|_| embed.description(description))
+            .modify_action_rows(|_| Vec::new());
+        if reason == EndReason::Shutdown {
+            message.content = Some(String::from(
+                "⏸️ This roll was resolved early because Patbot shut down.",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// The highest die that counts towards the roll's total
+fn highest_die(roll: &Roll) -> Option<u32> {
+    roll.terms
+        .iter()
+        .filter(|term| !term.negative)
+        .filter_map(|term| match &term.kind {
+            RolledKind::Dice { rolls, .. } => rolls
+                .iter()
+                .filter(|roll| roll.kept)
+                .map(|roll| roll.value)
+                .max(),
+            RolledKind::Constant(_) => None,
+        })
+        .max()
+}
+
+#[tokio::test]
+async fn test_group_roll_session() {
+    use crate::interactive::fake::{self, FakeGateway};
+
+    let data = fake::data();
+    let ctx = fake::session_context(&data, 1);
+    let mut group = GroupRoll {
+        title: String::from("Sneaking past the guards"),
+        embed_template: serenity::CreateEmbed::default(),
+        kind: GroupKind::Pool {
+            dice: String::from("2d6"),
+            resolve: PoolResolve::Highest,
+        },
+        sides: Vec::new(),
+        expected: Some(3),
+    };
+
+    let mut gateway = FakeGateway::new();
+    gateway
+        .click(2, "1_group_roll")
+        // Each person only rolls once
+        .click(2, "1_group_roll")
+        // Only the person who started the pool can resolve it early
+        .click(3, "1_group_resolve")
+        .click(3, "1_group_roll")
+        .click(1, "1_group_resolve");
+
+    let message = InteractiveMessage::new()
+        .embed(serenity::CreateEmbed::default())
+        .action_rows(Vec::new());
+    let config = InteractiveConfig {
+        duration: Duration::from_secs(60),
+        update_interval: Duration::ZERO,
+    };
+    let ended = message
+        .run_with(&ctx, &mut gateway, config, &mut group)
+        .await
+        .unwrap();
+
+    assert_eq!(ended.reason, EndReason::Completed);
+    assert_eq!(gateway.ephemerals(), 2);
+    let users: Vec<_> = group.sides.iter().map(|side| side.user.get()).collect();
+    assert_eq!(users, [2, 3]);
+    assert_eq!(group.rolled().count(), 2);
+    assert!(!group.is_complete());
+
+    let embed = gateway.embed();
+    assert_eq!(embed["title"], "[Resolved] Sneaking past the guards");
+    assert!(embed["description"]
+        .as_str()
+        .unwrap()
+        .starts_with("Highest die: **"));
+    assert_eq!(embed["footer"]["text"], "2/3 rolled");
+}
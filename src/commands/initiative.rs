@@ -0,0 +1,506 @@
+use super::dice::{fair_roll, FairRollKind, Rolled};
+use crate::{
+    commands::poll,
+    dice::Expr,
+    interactive::{
+        Config as InteractiveConfig, ControlFlow, EndReason, Interaction, InteractionKind,
+        Interactive, InteractiveMessage, Permission, SessionContext,
+    },
+    prelude::*,
+};
+use std::time::Duration;
+
+const INITIATIVE_DURATION: Duration = Duration::from_secs(60 * 60 * 8);
+/// Discord limits select menus to 25 options
+const MAX_COMBATANTS: usize = 25;
+
+/// Tracks the turn order of a fight. Whoever starts it runs the fight.
+#[poise::command(slash_command, guild_only)]
+pub async fn initiative(
+    ctx: ApplicationContext<'_>,
+    #[description = "What the fight is called"] title: Option<String>,
+) -> Result {
+    let author = poll::embed_author(ctx).await;
+    let embed_template = serenity::CreateEmbed::default()
+        .color(ctx.data().bot_color())
+        .author(author);
+
+    let mut initiative = Initiative {
+        title: title.unwrap_or_else(|| String::from("Initiative")),
+        embed_template,
+        combatants: Vec::new(),
+        delayed: Vec::new(),
+        turn: 0,
+        round: 0,
+        next_id: 0,
+    };
+
+    let message = InteractiveMessage::new()
+        .embed(initiative.embed())
+        .action_rows(initiative.action_rows(ctx.id()));
+    let config = InteractiveConfig {
+        duration: INITIATIVE_DURATION,
+        update_interval: Duration::from_secs(1),
+    };
+    initiative.run(ctx, config, message).await?;
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
+struct Combatant {
+    /// Stays the same when the turn order changes, so that select menus can refer to it
+    id: u32,
+    name: String,
+    owner: serenity::UserId,
+    initiative: i64,
+    /// The expression that was rolled, if the initiative wasn't a fixed value
+    rolled: Option<String>,
+}
+
+struct Initiative {
+    title: String,
+    embed_template: serenity::CreateEmbed,
+    /// Everyone in the fight, in turn order
+    combatants: Vec<Combatant>,
+    /// Combatants who are waiting to act until they're ready
+    delayed: Vec<Combatant>,
+    turn: usize,
+    /// 0 until the first turn starts
+    round: u32,
+    next_id: u32,
+}
+
+impl Initiative {
+    fn join(
+        &mut self,
+        name: String,
+        owner: serenity::UserId,
+        initiative: i64,
+        rolled: Option<String>,
+    ) {
+        let combatant = Combatant {
+            id: self.next_id,
+            name,
+            owner,
+            initiative,
+            rolled,
+        };
+        self.next_id += 1;
+
+        // Ties go to whoever joined first
+        let index = self
+            .combatants
+            .iter()
+            .position(|c| c.initiative < initiative)
+            .unwrap_or(self.combatants.len());
+        // Joining ahead of the current turn means waiting until the next round
+        if self.round > 0 && index <= self.turn && !self.combatants.is_empty() {
+            self.turn += 1;
+        }
+        self.combatants.insert(index, combatant);
+    }
+
+    fn current(&self) -> Option<&Combatant> {
+        if self.round == 0 {
+            return None;
+        }
+        self.combatants.get(self.turn)
+    }
+
+    fn next_turn(&mut self) {
+        if self.round == 0 {
+            self.round = 1;
+            self.turn = 0;
+            return;
+        }
+        self.turn += 1;
+        self.wrap_turn();
+    }
+
+    fn previous_turn(&mut self) {
+        if self.turn > 0 {
+            self.turn -= 1;
+        } else if self.round > 1 {
+            self.round -= 1;
+            self.turn = self.combatants.len().saturating_sub(1);
+        }
+    }
+
+    /// Starts the next round if the last combatant's turn is over
+    fn wrap_turn(&mut self) {
+        if self.turn >= self.combatants.len() && !self.combatants.is_empty() {
+            self.turn = 0;
+            self.round += 1;
+        }
+    }
+
+    /// Takes the current combatant out of the turn order until they're ready
+    fn delay(&mut self) {
+        let combatant = self.combatants.remove(self.turn);
+        self.delayed.push(combatant);
+        self.wrap_turn();
+    }
+
+    /// Puts a delayed combatant back into the turn order, acting immediately
+    fn ready(&mut self, index: usize) {
+        let mut combatant = self.delayed.remove(index);
+        match self.combatants.get(self.turn) {
+            Some(current) => {
+                combatant.initiative = current.initiative;
+                self.combatants.insert(self.turn, combatant);
+            }
+            None => {
+                self.turn = self.combatants.len();
+                self.combatants.push(combatant);
+            }
+        }
+    }
+
+    fn remove(&mut self, id: u32) -> Option<Combatant> {
+        if let Some(index) = self.delayed.iter().position(|c| c.id == id) {
+            return Some(self.delayed.remove(index));
+        }
+
+        let index = self.combatants.iter().position(|c| c.id == id)?;
+        let combatant = self.combatants.remove(index);
+        if index < self.turn {
+            self.turn -= 1;
+        } else if index == self.turn && self.round > 0 {
+            self.wrap_turn();
+        }
+        Some(combatant)
+    }
+
+    /// Whether the user may act for the combatant. Whoever runs the fight can act for anyone.
+    fn controls(
+        ctx: &SessionContext<'_>,
+        user_id: serenity::UserId,
+        combatant: &Combatant,
+    ) -> bool {
+        combatant.owner == user_id || ctx.author_id() == user_id
+    }
+
+    fn embed(&self) -> serenity::CreateEmbed {
+        let current = self.current().map(|c| c.id);
+        let lines: Vec<_> = self
+            .combatants
+            .iter()
+            .map(|c| {
+                let marker = if Some(c.id) == current {
+                    "▶️"
+                } else {
+                    "▫️"
+                };
+                format!("{marker} **{}** {}{}", c.initiative, c.name, rolled_text(c))
+            })
+            .collect();
+        let description = if lines.is_empty() {
+            String::from("Nobody has joined yet. Press **Join** to roll initiative.")
+        } else {
+            lines.join("\n")
+        };
+
+        let footer = match self.current() {
+            Some(current) => format!("Round {} • {}'s turn", self.round, current.name),
+            None if self.round > 0 => format!("Round {}", self.round),
+            None => String::from("Press Next to start the first round"),
+        };
+
+        let mut embed = self
+            .embed_template
+            .clone()
+            .title(self.title.clone())
+            .description(description)
+            .footer(serenity::CreateEmbedFooter::new(footer));
+        if !self.delayed.is_empty() {
+            let delayed = self
+                .delayed
+                .iter()
+                .map(|c| c.name.clone())
+                .collect::<Vec<_>>()
+                .join(", ");
+            embed = embed.field("Delayed", delayed, false);
+        }
+        embed
+    }
+
+    fn action_rows(&self, id: u64) -> Vec<serenity::CreateActionRow> {
+        let button = |name: &str, emoji: &str, label: &str, style: serenity::ButtonStyle| {
+            serenity::CreateButton::new(custom_id(id, name))
+                .emoji(emoji.parse::<serenity::ReactionType>().unwrap())
+                .label(label)
+                .style(style)
+        };
+        let mut rows = vec![serenity::CreateActionRow::Buttons(vec![
+            button("join", "🎲", "Join", serenity::ButtonStyle::Success),
+            button("prev", "◀️", "Previous", serenity::ButtonStyle::Secondary)
+                .disabled(self.round <= 1 && self.turn == 0),
+            button("next", "▶️", "Next", serenity::ButtonStyle::Primary)
+                .disabled(self.combatants.is_empty()),
+            button("delay", "⏳", "Delay", serenity::ButtonStyle::Secondary)
+                .disabled(self.current().is_none()),
+            button("end", "🛑", "End", serenity::ButtonStyle::Danger),
+        ])];
+
+        // Select menus can't be empty, so they're only shown when there is something to choose
+        let menu = |name: &str, placeholder: &str, combatants: Vec<&Combatant>| {
+            let options = combatants
+                .into_iter()
+                .map(|c| serenity::CreateSelectMenuOption::new(c.name.clone(), c.id.to_string()))
+                .collect();
+            let menu = serenity::CreateSelectMenu::new(
+                custom_id(id, name),
+                serenity::CreateSelectMenuKind::String { options },
+            )
+            .placeholder(placeholder);
+            serenity::CreateActionRow::SelectMenu(menu)
+        };
+        if !self.delayed.is_empty() && self.round > 0 {
+            rows.push(menu(
+                "ready",
+                "Ready a delayed combatant",
+                self.delayed.iter().collect(),
+            ));
+        }
+        let everyone: Vec<_> = self.combatants.iter().chain(&self.delayed).collect();
+        if !everyone.is_empty() {
+            rows.push(menu("remove", "Remove a combatant", everyone));
+        }
+        rows
+    }
+}
+
+fn rolled_text(combatant: &Combatant) -> String {
+    match &combatant.rolled {
+        Some(expression) => format!(" (`{expression}`)"),
+        None => String::new(),
+    }
+}
+
+fn custom_id(id: u64, name: &str) -> String {
+    format!("{id}_initiative_{name}")
+}
+
+impl Interactive for Initiative {
+    const KIND: &'static str = "initiative";
+
+    /// Only the person running the fight moves it along. Delaying and readying are checked when
+    /// they're processed, since players may do that for their own combatants.
+    fn permission(&self, custom_id: &str) -> Permission {
+        match custom_id.rsplit_once('_') {
+            Some((_, "next" | "prev" | "remove" | "end")) => Permission::Author,
+            _ => Permission::Everyone,
+        }
+    }
+
+    async fn process(
+        &mut self,
+        ctx: &SessionContext<'_>,
+        interaction: &Interaction,
+    ) -> Result<ControlFlow> {
+        let user_id = interaction.user_id;
+        let (_, name) = interaction.custom_id.rsplit_once('_').unwrap();
+        match (&interaction.kind, name) {
+            (InteractionKind::Button, "join") => {
+                if self.combatants.len() + self.delayed.len() >= MAX_COMBATANTS {
+                    return Ok(ControlFlow::ephemeral(format!(
+                        ":x: A fight can have at most {MAX_COMBATANTS} combatants."
+                    )));
+                }
+                let modal_custom_id = custom_id(ctx.id(), "join_modal");
+                return Ok(ControlFlow::modal::<JoinModal>(modal_custom_id, None));
+            }
+            (InteractionKind::Button, "next") => self.next_turn(),
+            (InteractionKind::Button, "prev") => self.previous_turn(),
+            (InteractionKind::Button, "delay") => {
+                let Some(current) = self.current() else {
+                    return Ok(ControlFlow::ephemeral(":x: The fight hasn't started yet."));
+                };
+                if !Self::controls(ctx, user_id, current) {
+                    return Ok(ControlFlow::ephemeral(
+                        ":x: You can only delay on your own turn.",
+                    ));
+                }
+                self.delay();
+            }
+            (InteractionKind::Button, "end") => return Ok(ControlFlow::Break),
+            (InteractionKind::Select { values }, "ready") => {
+                let id = values.first().and_then(|value| value.parse().ok());
+                let Some(index) = self.delayed.iter().position(|c| Some(c.id) == id) else {
+                    return Ok(ControlFlow::ephemeral(":x: They aren't delaying anymore."));
+                };
+                if !Self::controls(ctx, user_id, &self.delayed[index]) {
+                    return Ok(ControlFlow::ephemeral(
+                        ":x: You can only ready your own combatants.",
+                    ));
+                }
+                self.ready(index);
+            }
+            (InteractionKind::Select { values }, "remove") => {
+                let id = values.first().and_then(|value| value.parse().ok());
+                if id.and_then(|id| self.remove(id)).is_none() {
+                    return Ok(ControlFlow::ephemeral(":x: They already left the fight."));
+                }
+            }
+            _ => unreachable!(),
+        }
+        Ok(ControlFlow::Continue { update: true })
+    }
+
+    async fn process_modal(
+        &mut self,
+        ctx: &SessionContext<'_>,
+        interaction: &Interaction,
+    ) -> Result<ControlFlow> {
+        let InteractionKind::Modal(data) = &interaction.kind else {
+            unreachable!()
+        };
+        let JoinModal { name, initiative } = match <JoinModal as poise::Modal>::parse(data.clone())
+        {
+            Ok(modal) => modal,
+            Err(err) => return Ok(ControlFlow::ephemeral(format!(":x: {err}"))),
+        };
+
+        // The name and `$stats` in the initiative come from the user's current character
+        let user_id = interaction.user_id;
+        let (character_name, dice) = ctx.data().use_characters(|c| {
+            let character_name = c.get(user_id, None).map(|sheet| sheet.name.clone());
+            (character_name, c.substitute(user_id, &initiative))
+        });
+        let Some(name) = name
+            .map(|name| name.trim().to_owned())
+            .filter(|name| !name.is_empty())
+            .or(character_name)
+        else {
+            return Ok(ControlFlow::ephemeral(
+                ":x: Enter a name, or create a character with `/character create`.",
+            ));
+        };
+        let dice = match dice {
+            Ok(dice) => dice,
+            Err(err) => return Ok(ControlFlow::ephemeral(format!(":x: {err}"))),
+        };
+        let expr = match dice.parse::<Expr>() {
+            Ok(expr) => expr,
+            Err(err) => return Ok(ControlFlow::ephemeral(format!(":x: {err}"))),
+        };
+
+        let initiative = initiative.trim();
+        let (total, rolled) = match initiative.parse::<i64>() {
+            Ok(total) => (total, None),
+            Err(_) => {
+                let kind = FairRollKind::Dice {
+                    expression: expr.to_string(),
+                };
+                let (id, Rolled::Dice(roll)) = fair_roll(ctx.data(), user_id, kind, false) else {
+                    unreachable!()
+                };
+                let rolled = format!("{initiative} = {} (roll #{id})", roll.total);
+                (roll.total, Some(rolled))
+            }
+        };
+        self.join(name, user_id, total, rolled);
+        Ok(ControlFlow::Continue { update: true })
+    }
+
+    fn update(&mut self, ctx: &SessionContext<'_>, message: &mut InteractiveMessage) {
+        let embed = self.embed();
+        let action_rows = self.action_rows(ctx.id());
+        message
+            .modify_embed(|_| embed)
+            .modify_action_rows(|_| action_rows);
+    }
+
+    async fn finish(
+        &mut self,
+        _ctx: &SessionContext<'_>,
+        message: &mut InteractiveMessage,
+        reason: EndReason,
+    ) -> Result<()> {
+        let embed = self.embed().title(format!("[Ended] {}", self.title));
+        message
+            .modify_embed(|_| embed)
+            .modify_action_rows(|_| Vec::new());
+        if reason == EndReason::Shutdown {
+            message.content = Some(String::from(
+                "⏸️ This fight was ended early because Patbot shut down.",
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[derive(poise::Modal, Debug)]
+#[name = "Join the fight"]
+struct JoinModal {
+    #[name = "Name"]
+    #[placeholder = "Defaults to your current character"]
+    #[max_length = 100]
+    name: Option<String>,
+    #[name = "Initiative"]
+    #[placeholder = "A roll like 1d20+$dex_mod, or a number like 15"]
+    initiative: String,
+}
+
+#[tokio::test]
+async fn test_initiative_session() {
+    use crate::interactive::fake::{self, FakeGateway};
+
+    let data = fake::data();
+    let ctx = fake::session_context(&data, 1);
+    let mut initiative = Initiative {
+        title: String::from("Ambush"),
+        embed_template: serenity::CreateEmbed::default(),
+        combatants: Vec::new(),
+        delayed: Vec::new(),
+        turn: 0,
+        round: 0,
+        next_id: 0,
+    };
+
+    let join = |name, value| [("name", name), ("initiative", value)];
+    let mut gateway = FakeGateway::new();
+    gateway
+        .submit(1, "1_initiative_join_modal", &join("Goblin", "12"))
+        .submit(2, "1_initiative_join_modal", &join("Aria", "18"))
+        .submit(3, "1_initiative_join_modal", &join("Bram", "15"))
+        // Only the person running the fight can start it
+        .click(2, "1_initiative_next")
+        .click(1, "1_initiative_next")
+        // Only Aria's player can delay on her turn
+        .click(3, "1_initiative_delay")
+        .click(2, "1_initiative_delay")
+        .click(1, "1_initiative_next")
+        .select(2, "1_initiative_ready", &["1"])
+        .click(1, "1_initiative_next")
+        .click(1, "1_initiative_next")
+        .select(1, "1_initiative_remove", &["0"])
+        .click(1, "1_initiative_end");
+
+    let message = InteractiveMessage::new()
+        .embed(serenity::CreateEmbed::default())
+        .action_rows(Vec::new());
+    let config = InteractiveConfig {
+        duration: Duration::from_secs(60),
+        update_interval: Duration::ZERO,
+    };
+    let ended = message
+        .run_with(&ctx, &mut gateway, config, &mut initiative)
+        .await
+        .unwrap();
+
+    assert_eq!(ended.reason, EndReason::Completed);
+    assert_eq!(gateway.ephemerals(), 2);
+    let names: Vec<_> = initiative
+        .combatants
+        .iter()
+        .map(|c| c.name.as_str())
+        .collect();
+    assert_eq!(names, ["Bram", "Aria"]);
+    assert_eq!(initiative.combatants[1].initiative, 12);
+    assert_eq!((initiative.round, initiative.turn), (2, 0));
+
+    let embed = gateway.embed();
+    assert_eq!(embed["title"], "[Ended] Ambush");
+    assert_eq!(embed["footer"]["text"], "Round 2 • Bram's turn");
+}
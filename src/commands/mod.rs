@@ -1,15 +1,25 @@
+mod character;
 mod counter;
 mod dice;
 mod dynamic;
+mod group_roll;
+mod initiative;
 mod petition;
 mod poll;
+mod sessions;
 mod utils;
 mod voice;
+mod wildsea;
 
+pub use character::*;
 pub use counter::*;
 pub use dice::*;
 pub use dynamic::*;
+pub use group_roll::*;
+pub use initiative::*;
 pub use petition::*;
 pub use poll::*;
+pub use sessions::*;
 pub use utils::*;
 pub use voice::*;
+pub use wildsea::*;
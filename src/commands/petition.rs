@@ -1,16 +1,39 @@
 use crate::{
-    commands::poll,
-    interactive::{Config as InteractiveConfig, ControlFlow, Interactive, InteractiveMessage},
+    commands::{counter, poll},
+    data::{Data, Delegations},
+    interactive::{
+        Config as InteractiveConfig, ControlFlow, EndReason, Interaction, InteractionKind,
+        Interactive, InteractiveMessage, Paginator, Permission, SessionContext,
+    },
     prelude::*,
 };
 use serenity::Mentionable;
 use std::collections::HashMap;
 
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommand_required,
+    subcommands("start", "delegate", "revoke", "audit")
+)]
+pub async fn petition(_ctx: ApplicationContext<'_>) -> Result {
+    unreachable!()
+}
+
 /// Create a Frodge petition
 #[poise::command(slash_command, guild_only)]
-pub async fn petition(
+pub async fn start(
     ctx: ApplicationContext<'_>,
     #[description = "The title of the petition"] title: String,
+    #[description = "Something Patbot should do if the petition passes"]
+    action: Option<PetitionActionKind>,
+    #[description = "The role to grant or revoke"] role: Option<serenity::Role>,
+    #[description = "The member to grant the role to or revoke it from"]
+    member: Option<serenity::User>,
+    #[description = "The name of the channel to create"] channel_name: Option<String>,
+    #[description = "The counter to increment"]
+    #[autocomplete = "counter::autocomplete_name"]
+    counter: Option<String>,
 ) -> Result {
     const PETITION_DURATION: std::time::Duration = std::time::Duration::from_secs(60 * 60 * 24);
 
@@ -30,8 +53,38 @@ pub async fn petition(
         );
     }
 
+    let options = PetitionActionOptions {
+        role: role.map(|role| role.id),
+        member: member.map(|member| member.id),
+        channel_name,
+        counter,
+    };
+    let action = match action {
+        None if options.any() => reply_error!(
+            ctx,
+            "The `role`, `member`, `channel_name` and `counter` options need an `action`."
+        ),
+        None => None,
+        Some(kind) => match PetitionAction::new(kind, options) {
+            Ok(action) => Some(action),
+            Err(message) => reply_error!(ctx, "{}", message),
+        },
+    };
+    if let Some(PetitionAction::IncrementCounter { name }) = &action {
+        if ctx.data().use_counters(|c| c.get(name).is_none()) {
+            reply_error!(ctx, "The counter `{}` does not exist.", name);
+        }
+    }
+
+    let id = ctx
+        .data()
+        .use_petitions_mut(|p| p.create(&title, ctx.author().id));
+
     let author = poll::embed_author(ctx).await;
-    let footer = poll::embed_footer(PETITION_DURATION);
+    let footer = serenity::CreateEmbedFooter::new(format!(
+        "Petition #{id} • {}",
+        poll::closing_time_text(PETITION_DURATION)
+    ));
 
     let embed_template = serenity::CreateEmbed::default()
         .color(serenity::Color::DARK_RED)
@@ -39,11 +92,13 @@ pub async fn petition(
         .footer(footer);
 
     let mut petition = Petition {
+        id,
         title: title.clone(),
         embed_template: embed_template.clone(),
-        vote_totals: [0; 3],
         votes: HashMap::default(),
+        delegations: eligible_delegations(ctx.data()),
         frodge_membership_count: crate::frodge_membership_count() as _,
+        action,
     };
 
     let embed = embed_template
@@ -54,15 +109,18 @@ pub async fn petition(
 
     let config = InteractiveConfig {
         duration: PETITION_DURATION,
+        update_interval: std::time::Duration::from_secs(2),
     };
 
     let message = InteractiveMessage::default()
         .embed(embed)
         .action_rows(action_rows);
 
-    let petition_message_id = petition.run(ctx, config, message).await?;
+    let ended = petition.run(ctx, config, message).await?;
+    let petition_message_id = ended.message_id;
 
-    if petition.passed() {
+    let completed = matches!(ended.reason, EndReason::Completed | EndReason::Expired);
+    if petition.passed() && completed {
         guild
             .congress_text_channel_id
             .pin(ctx, petition_message_id)
@@ -70,8 +128,25 @@ pub async fn petition(
     }
 
     let title = &petition.title;
-    let content = if petition.passed() {
-        format!("⚖️ The petition **{title}** passed!")
+    let content = if ended.reason == EndReason::Cancelled {
+        format!("⚖️ The petition **{title}** was cancelled.")
+    } else if ended.reason == EndReason::Shutdown {
+        format!("⚖️ The petition **{title}** was closed early because Patbot shut down.")
+    } else if petition.passed() {
+        match &petition.action {
+            Some(action) => {
+                let report = match action.execute(ctx, guild).await {
+                    Ok(report) => format!(":white_check_mark: {report}"),
+                    Err(err) => format!(":x: Failed to {}: {err}", action.description()),
+                };
+                format!("⚖️ The petition **{title}** passed!\n{report}")
+            }
+            None => format!("⚖️ The petition **{title}** passed!"),
+        }
+    } else if !petition.can_still_pass() {
+        format!(
+            "⚖️ The petition **{title}** failed because it can no longer receive enough support."
+        )
     } else if petition.total_votes() < petition.total_votes_needed() {
         format!("⚖️ The petition **{title}** failed because not enough people voted.")
     } else {
@@ -91,12 +166,310 @@ pub async fn petition(
     Ok(())
 }
 
+/// Delegate your petition votes to another Frodge member while you're away
+#[poise::command(slash_command, guild_only)]
+pub async fn delegate(
+    ctx: ApplicationContext<'_>,
+    #[description = "The Frodge member who will vote on your behalf"] member: serenity::User,
+    #[description = "How many days the delegation lasts. Default is 7"]
+    #[min = 1]
+    #[max = 30]
+    days: Option<u32>,
+) -> Result {
+    const DEFAULT_DELEGATION_DAYS: u32 = 7;
+
+    let author_id = ctx.author().id;
+    if !crate::is_frodge_member(author_id) {
+        reply_error!(ctx, "Only Frodge members can delegate their votes.");
+    }
+    if member.id == author_id {
+        reply_error!(ctx, "You cannot delegate your votes to yourself.");
+    }
+    if !crate::is_frodge_member(member.id) {
+        reply_error!(
+            ctx,
+            "{} is not a Frodge member, so they cannot vote on your behalf.",
+            member.mention()
+        );
+    }
+
+    let days = days.unwrap_or(DEFAULT_DELEGATION_DAYS);
+    let expires_at = time::OffsetDateTime::now_utc() + time::Duration::days(days.into());
+    ctx.data()
+        .use_delegations_mut(|d| d.delegate(author_id, member.id, expires_at));
+
+    ctx.send(
+        poise::CreateReply::default()
+            .content(format!(
+                ":white_check_mark: {} will vote on your behalf on any petition you don't vote on until <t:{}:f>.",
+                member.mention(),
+                expires_at.unix_timestamp()
+            ))
+            .reply(true)
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Revoke your petition vote delegation
+#[poise::command(slash_command, guild_only)]
+pub async fn revoke(ctx: ApplicationContext<'_>) -> Result {
+    let Some(delegation) = ctx
+        .data()
+        .use_delegations_mut(|d| d.revoke(ctx.author().id))
+    else {
+        reply_error!(ctx, "You have not delegated your votes to anyone.");
+    };
+
+    let delegate = serenity::UserId::new(delegation.delegate);
+    ctx.send(
+        poise::CreateReply::default()
+            .content(format!(
+                ":white_check_mark: {} will no longer vote on your behalf.",
+                delegate.mention()
+            ))
+            .reply(true)
+            .ephemeral(true),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Show the history of votes cast on a petition
+#[poise::command(slash_command, guild_only, owners_only, ephemeral)]
+pub async fn audit(
+    ctx: ApplicationContext<'_>,
+    #[description = "The number of the petition"] id: u32,
+) -> Result {
+    const AUDIT_LINES_PER_PAGE: usize = 20;
+
+    let Some(record) = ctx.data().use_petitions(|p| p.get(id).cloned()) else {
+        reply_error!(ctx, "There is no petition #{}.", id);
+    };
+
+    let mut lines: Vec<_> = record
+        .audit_log
+        .iter()
+        .map(|change| {
+            let user = serenity::UserId::new(change.user).mention();
+            let timestamp = change.at.unix_timestamp();
+            match change.previous {
+                Some(previous) => {
+                    format!("<t:{timestamp}:f> {user}: {previous} → {}", change.new)
+                }
+                None => format!("<t:{timestamp}:f> {user}: voted {}", change.new),
+            }
+        })
+        .collect();
+    if lines.is_empty() {
+        lines.push(String::from("Nobody has voted on this petition."));
+    }
+
+    let title = format!("Petition #{id}: {}", record.title);
+    let author = crate::get_frodge_member(serenity::UserId::new(record.author));
+    Paginator::from_lines(ctx, title, lines, AUDIT_LINES_PER_PAGE)
+        .footer(format!("Started by {}", author.unwrap_or("unknown")))
+        .send(ctx)
+        .await
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum Vote {
+    Yay,
+    Nay,
+    Meh,
+}
+
+impl Vote {
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+impl std::fmt::Display for Vote {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Yay => "Yay",
+            Self::Nay => "Nay",
+            Self::Meh => "Meh",
+        };
+        f.write_str(name)
+    }
+}
+
+/// We use u64 instead of serenity::UserId because serenity's IDs don't round-trip through
+/// `shuttle_persist`.
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct PetitionRecord {
+    pub id: u32,
+    pub title: String,
+    pub author: u64,
+    pub created_at: time::OffsetDateTime,
+    pub audit_log: Vec<VoteChange>,
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub struct VoteChange {
+    pub user: u64,
+    pub previous: Option<Vote>,
+    pub new: Vote,
+    pub at: time::OffsetDateTime,
+}
+
+#[derive(poise::ChoiceParameter, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum PetitionActionKind {
+    #[name = "grant role"]
+    GrantRole,
+    #[name = "revoke role"]
+    RevokeRole,
+    #[name = "create channel"]
+    CreateChannel,
+    #[name = "increment counter"]
+    IncrementCounter,
+}
+
+struct PetitionActionOptions {
+    role: Option<serenity::RoleId>,
+    member: Option<serenity::UserId>,
+    channel_name: Option<String>,
+    counter: Option<String>,
+}
+
+impl PetitionActionOptions {
+    fn any(&self) -> bool {
+        self.role.is_some()
+            || self.member.is_some()
+            || self.channel_name.is_some()
+            || self.counter.is_some()
+    }
+}
+
+/// Something Patbot does automatically when a petition passes
+enum PetitionAction {
+    GrantRole {
+        role: serenity::RoleId,
+        member: serenity::UserId,
+    },
+    RevokeRole {
+        role: serenity::RoleId,
+        member: serenity::UserId,
+    },
+    CreateChannel {
+        name: String,
+    },
+    IncrementCounter {
+        name: String,
+    },
+}
+
+impl PetitionAction {
+    fn new(kind: PetitionActionKind, options: PetitionActionOptions) -> Result<Self, &'static str> {
+        let PetitionActionOptions {
+            role,
+            member,
+            channel_name,
+            counter,
+        } = options;
+        Ok(match kind {
+            PetitionActionKind::GrantRole | PetitionActionKind::RevokeRole => {
+                if channel_name.is_some() || counter.is_some() {
+                    return Err("Only `role` and `member` can be chosen for this action.");
+                }
+                let (Some(role), Some(member)) = (role, member) else {
+                    return Err("You must choose both a `role` and a `member` for this action.");
+                };
+                if kind == PetitionActionKind::GrantRole {
+                    Self::GrantRole { role, member }
+                } else {
+                    Self::RevokeRole { role, member }
+                }
+            }
+            PetitionActionKind::CreateChannel => {
+                if role.is_some() || member.is_some() || counter.is_some() {
+                    return Err("Only `channel_name` can be chosen for this action.");
+                }
+                let Some(name) = channel_name else {
+                    return Err("You must choose a `channel_name` for this action.");
+                };
+                if name.is_empty() || name.len() > 100 {
+                    return Err("Channel names must be between 1 and 100 characters long.");
+                }
+                Self::CreateChannel { name }
+            }
+            PetitionActionKind::IncrementCounter => {
+                if role.is_some() || member.is_some() || channel_name.is_some() {
+                    return Err("Only `counter` can be chosen for this action.");
+                }
+                let Some(name) = counter else {
+                    return Err("You must choose a `counter` for this action.");
+                };
+                Self::IncrementCounter { name }
+            }
+        })
+    }
+
+    fn description(&self) -> String {
+        match self {
+            Self::GrantRole { role, member } => {
+                format!("grant {} to {}", role.mention(), member.mention())
+            }
+            Self::RevokeRole { role, member } => {
+                format!("revoke {} from {}", role.mention(), member.mention())
+            }
+            Self::CreateChannel { name } => format!("create the channel `#{name}`"),
+            Self::IncrementCounter { name } => format!("increment the counter `{name}`"),
+        }
+    }
+
+    /// Returns a report of what was done
+    async fn execute(&self, ctx: ApplicationContext<'_>, guild: PatbotGuild) -> Result<String> {
+        const AUDIT_LOG_REASON: &str = "Frodge petition passed";
+
+        Ok(match self {
+            Self::GrantRole { role, member } => {
+                ctx.http()
+                    .add_member_role(guild.id, *member, *role, Some(AUDIT_LOG_REASON))
+                    .await?;
+                format!("Granted {} to {}.", role.mention(), member.mention())
+            }
+            Self::RevokeRole { role, member } => {
+                ctx.http()
+                    .remove_member_role(guild.id, *member, *role, Some(AUDIT_LOG_REASON))
+                    .await?;
+                format!("Revoked {} from {}.", role.mention(), member.mention())
+            }
+            Self::CreateChannel { name } => {
+                let channel = guild
+                    .id
+                    .create_channel(
+                        ctx,
+                        serenity::CreateChannel::new(name.clone())
+                            .kind(serenity::ChannelType::Text)
+                            .audit_log_reason(AUDIT_LOG_REASON),
+                    )
+                    .await?;
+                format!("Created {}.", channel.mention())
+            }
+            Self::IncrementCounter { name } => {
+                let Some(value) = ctx.data().use_counters_mut(|c| c.add(name, 1)) else {
+                    return Err(format!("the counter `{name}` no longer exists").into());
+                };
+                format!("Incremented counter `{name}`. New value is `{value}`.")
+            }
+        })
+    }
+}
+
 struct Petition {
+    id: u32,
     title: String,
     embed_template: serenity::CreateEmbed,
-    vote_totals: [u32; 3],
-    votes: HashMap<serenity::UserId, usize>,
+    votes: HashMap<serenity::UserId, Vote>,
+    /// Maps members who delegated their vote to the member voting on their behalf
+    delegations: HashMap<serenity::UserId, serenity::UserId>,
     frodge_membership_count: u32,
+    action: Option<PetitionAction>,
 }
 
 impl Petition {
@@ -112,72 +485,130 @@ impl Petition {
         (self.frodge_membership_count as f64 * 2. / 3.).ceil() as _
     }
 
+    /// Votes cast directly by members
+    fn direct_vote_totals(&self) -> [u32; 3] {
+        let mut totals = [0; 3];
+        for &vote in self.votes.values() {
+            totals[vote.index()] += 1;
+        }
+        totals
+    }
+
+    /// Votes cast by delegates on behalf of members who haven't voted themselves
+    fn delegated_vote_totals(&self) -> [u32; 3] {
+        let mut totals = [0; 3];
+        for (delegator, delegate) in &self.delegations {
+            if self.votes.contains_key(delegator) {
+                continue;
+            }
+            if let Some(&vote) = self.votes.get(delegate) {
+                totals[vote.index()] += 1;
+            }
+        }
+        totals
+    }
+
+    fn vote_totals(&self) -> [u32; 3] {
+        let direct = self.direct_vote_totals();
+        let delegated = self.delegated_vote_totals();
+        std::array::from_fn(|i| direct[i] + delegated[i])
+    }
+
     fn total_votes(&self) -> u32 {
-        let [yays, nays, mehs] = self.vote_totals;
-        yays + nays + mehs
+        self.vote_totals().iter().sum()
     }
 
     fn passed(&self) -> bool {
-        self.vote_totals[0] >= self.total_yays_needed()
+        self.vote_totals()[Vote::Yay.index()] >= self.total_yays_needed()
+    }
+
+    /// Whether enough members are left to vote for the petition to pass. This assumes that
+    /// members who have already voted won't change their votes.
+    fn can_still_pass(&self) -> bool {
+        let yays = self.vote_totals()[Vote::Yay.index()];
+        let members_left = self
+            .frodge_membership_count
+            .saturating_sub(self.total_votes());
+        yays + members_left >= self.total_yays_needed()
+    }
+
+    fn outcome_decided(&self) -> bool {
+        self.remaining_votes_needed() == 0 || self.passed() || !self.can_still_pass()
     }
 
     fn build_embed_fields(&self, done: bool) -> impl IntoIterator<Item = (String, String, bool)> {
-        let [yays, nays, mehs] = self.vote_totals;
-        let remaining_votes = self.remaining_votes_needed();
-        if done {
-            vec![
-                (String::from("Yay"), yays.to_string(), true),
-                (String::from("Nay"), nays.to_string(), true),
-                (String::from("Meh"), mehs.to_string(), true),
-            ]
-        } else {
-            vec![
-                (String::from("Yay"), yays.to_string(), true),
-                (String::from("Nay"), nays.to_string(), true),
-                (String::from("Meh"), mehs.to_string(), true),
-                (
-                    String::from("Votes Needed"),
-                    remaining_votes.to_string(),
-                    false,
-                ),
-            ]
+        let direct = self.direct_vote_totals();
+        let delegated = self.delegated_vote_totals();
+        let field = |vote: Vote| {
+            let i = vote.index();
+            let value = match delegated[i] {
+                0 => direct[i].to_string(),
+                n => format!("{} (+{n} delegated)", direct[i]),
+            };
+            (vote.to_string(), value, true)
+        };
+
+        let mut fields = vec![field(Vote::Yay), field(Vote::Nay), field(Vote::Meh)];
+        if let Some(action) = &self.action {
+            let mut description = action.description();
+            // Capitalize the first letter of the action description
+            description[..1].make_ascii_uppercase();
+            fields.push((String::from("If Passed"), description, false));
         }
+        if !done {
+            fields.push((
+                String::from("Votes Needed"),
+                self.remaining_votes_needed().to_string(),
+                false,
+            ));
+        }
+        fields
     }
 }
 
 impl Interactive for Petition {
+    const KIND: &'static str = "petition";
+
+    /// Only Frodge members count towards the petition's thresholds, so nobody else may vote
+    fn permission(&self, _custom_id: &str) -> Permission {
+        Permission::FrodgeMembers
+    }
+
     async fn process(
         &mut self,
-        _ctx: ApplicationContext<'_>,
-        interaction: &serenity::ComponentInteraction,
+        ctx: &SessionContext<'_>,
+        interaction: &Interaction,
     ) -> Result<ControlFlow> {
-        assert!(matches!(
-            &interaction.data.kind,
-            serenity::ComponentInteractionDataKind::Button
-        ));
-        let new_vote: usize = match interaction.data.custom_id.rsplit_once('_') {
-            Some((_, "yay")) => 0,
-            Some((_, "nay")) => 1,
-            Some((_, "meh")) => 2,
+        assert!(matches!(interaction.kind, InteractionKind::Button));
+        let new_vote = match interaction.custom_id.rsplit_once('_') {
+            Some((_, "yay")) => Vote::Yay,
+            Some((_, "nay")) => Vote::Nay,
+            Some((_, "meh")) => Vote::Meh,
             _ => unreachable!(),
         };
 
-        self.vote_totals[new_vote] += 1;
-        if let Some(prev_vote) = self.votes.get_mut(&interaction.user.id) {
-            self.vote_totals[*prev_vote] -= 1;
-            *prev_vote = new_vote;
-        } else {
-            self.votes.insert(interaction.user.id, new_vote);
+        // Delegations can be made or revoked while the petition is open
+        self.delegations = eligible_delegations(ctx.data());
+        let previous_vote = self.votes.insert(interaction.user_id, new_vote);
+        if previous_vote != Some(new_vote) {
+            let change = VoteChange {
+                user: interaction.user_id.get(),
+                previous: previous_vote,
+                new: new_vote,
+                at: time::OffsetDateTime::now_utc(),
+            };
+            ctx.data()
+                .use_petitions_mut(|p| p.record_vote_change(self.id, change));
         }
 
-        Ok(if self.remaining_votes_needed() == 0 {
+        Ok(if self.outcome_decided() {
             ControlFlow::Break
         } else {
             ControlFlow::Continue { update: true }
         })
     }
 
-    fn update(&mut self, _ctx: ApplicationContext<'_>, message: &mut InteractiveMessage) {
+    fn update(&mut self, _ctx: &SessionContext<'_>, message: &mut InteractiveMessage) {
         let embed = self
             .embed_template
             .clone()
@@ -188,11 +619,16 @@ impl Interactive for Petition {
 
     async fn finish(
         &mut self,
-        _ctx: ApplicationContext<'_>,
+        _ctx: &SessionContext<'_>,
         message: &mut InteractiveMessage,
+        reason: EndReason,
     ) -> Result<()> {
-        let passed = self.passed();
-        let title_prefix = if passed { "Passed" } else { "Failed" };
+        let title_prefix = match reason {
+            EndReason::Cancelled => "Cancelled",
+            EndReason::Shutdown => "Closed",
+            _ if self.passed() => "Passed",
+            _ => "Failed",
+        };
         let title = format!("[{title_prefix}] {}", self.title);
 
         let embed = self
@@ -208,6 +644,15 @@ impl Interactive for Petition {
     }
 }
 
+/// Active delegations where both members are eligible to vote on petitions
+fn eligible_delegations(data: &Data) -> HashMap<serenity::UserId, serenity::UserId> {
+    let delegations = data.use_delegations(Delegations::active);
+    delegations
+        .into_iter()
+        .filter(|&(from, to)| crate::is_frodge_member(from) && crate::is_frodge_member(to))
+        .collect()
+}
+
 fn petition_action_rows(ctx: ApplicationContext<'_>) -> Vec<serenity::CreateActionRow> {
     vec![serenity::CreateActionRow::Buttons(vec![
         serenity::CreateButton::new(custom_id(ctx, "yay"))
@@ -229,3 +674,107 @@ fn custom_id(ctx: ApplicationContext<'_>, name: &str) -> String {
     let id = ctx.id();
     format!("{id}_petition_{name}")
 }
+
+#[test]
+fn test_petition_early_close() {
+    fn vote(petition: &mut Petition, user: u64, vote: Vote) {
+        petition.votes.insert(serenity::UserId::new(user), vote);
+    }
+
+    let mut petition = Petition {
+        id: 1,
+        title: String::from("Test"),
+        embed_template: serenity::CreateEmbed::default(),
+        votes: HashMap::default(),
+        delegations: HashMap::default(),
+        frodge_membership_count: 9,
+        action: None,
+    };
+
+    // 9 members need 6 yays to pass, so 4 nays decide the outcome
+    for user in 1..=3 {
+        vote(&mut petition, user, Vote::Nay);
+    }
+    assert!(!petition.outcome_decided());
+    vote(&mut petition, 4, Vote::Nay);
+    assert!(!petition.can_still_pass());
+    assert!(petition.outcome_decided());
+
+    petition.votes.clear();
+    for user in 1..=5 {
+        vote(&mut petition, user, Vote::Yay);
+    }
+    assert!(!petition.outcome_decided());
+    vote(&mut petition, 6, Vote::Yay);
+    assert!(petition.passed());
+    assert!(petition.outcome_decided());
+}
+
+#[test]
+fn test_petition_action_options() {
+    let options = || PetitionActionOptions {
+        role: None,
+        member: None,
+        channel_name: None,
+        counter: Some(String::from("frogs")),
+    };
+    assert!(PetitionAction::new(PetitionActionKind::IncrementCounter, options()).is_ok());
+    // Options for another action aren't silently dropped
+    assert!(PetitionAction::new(PetitionActionKind::CreateChannel, options()).is_err());
+    let options = PetitionActionOptions {
+        channel_name: Some(String::from("frogs")),
+        ..options()
+    };
+    assert!(PetitionAction::new(PetitionActionKind::CreateChannel, options).is_err());
+}
+
+#[tokio::test]
+async fn test_petition_session_passes() {
+    use crate::interactive::fake::{self, FakeGateway};
+
+    let data = fake::data();
+    let ctx = fake::session_context(&data, 1);
+    let id = data.use_petitions_mut(|p| p.create("Test", serenity::UserId::new(1)));
+    let mut petition = Petition {
+        id,
+        title: String::from("Test"),
+        embed_template: serenity::CreateEmbed::default(),
+        votes: HashMap::default(),
+        delegations: HashMap::default(),
+        frodge_membership_count: 9,
+        action: None,
+    };
+
+    // Only Frodge members may vote, and the petition closes as soon as it has 6 yays
+    let mut gateway = FakeGateway::new();
+    gateway.click(100, "1_petition_yay");
+    gateway.click(1, "1_petition_nay");
+    for user in 1..=7 {
+        gateway.click(user, "1_petition_yay");
+    }
+
+    let message = InteractiveMessage::new()
+        .embed(serenity::CreateEmbed::default())
+        .action_rows(Vec::new());
+    let config = InteractiveConfig {
+        duration: std::time::Duration::from_secs(60),
+        update_interval: std::time::Duration::ZERO,
+    };
+    let ended = message
+        .run_with(&ctx, &mut gateway, config, &mut petition)
+        .await
+        .unwrap();
+
+    assert_eq!(ended.reason, EndReason::Completed);
+    assert_eq!(gateway.ephemerals(), 1);
+    assert!(petition.passed());
+    assert!(!petition.votes.contains_key(&serenity::UserId::new(7)));
+
+    let embed = gateway.embed();
+    assert_eq!(embed["title"], "[Passed] Test");
+    assert_eq!(embed["fields"][0]["value"], "6");
+    assert!(gateway.message.unwrap().action_rows.unwrap().is_empty());
+
+    let audit_log = data.use_petitions(|p| p.get(id).unwrap().audit_log.len());
+    assert_eq!(audit_log, 7);
+}
@@ -1,5 +1,8 @@
 use crate::{
-    interactive::{Config as InteractiveConfig, ControlFlow, Interactive, InteractiveMessage},
+    interactive::{
+        Config as InteractiveConfig, ControlFlow, EndReason, Interaction, InteractionKind,
+        Interactive, InteractiveMessage, Permission, SessionContext,
+    },
     prelude::*,
 };
 use parse_display::FromStr;
@@ -128,6 +131,7 @@ pub async fn poll(ctx: ApplicationContext<'_>) -> Result {
 
     let config = InteractiveConfig {
         duration: duration.into(),
+        update_interval: std::time::Duration::from_secs(2),
     };
 
     message.run(ctx, config, &mut poll).await?;
@@ -160,6 +164,10 @@ pub(in crate::commands) async fn embed_author(
 pub(in crate::commands) fn embed_footer(
     duration: std::time::Duration,
 ) -> serenity::CreateEmbedFooter {
+    serenity::CreateEmbedFooter::new(closing_time_text(duration))
+}
+
+pub(in crate::commands) fn closing_time_text(duration: std::time::Duration) -> String {
     // Format the poll expiration time
     let time_zone = chrono::FixedOffset::west_opt(4 * 60 * 60).unwrap();
     let start_time = chrono::Utc::now().with_timezone(&time_zone);
@@ -167,7 +175,7 @@ pub(in crate::commands) fn embed_footer(
     let expiration_time = start_time.checked_add_signed(duration).unwrap();
     let formatted_expiration_time = expiration_time.format("%-I:%M %p EST on %e %B %Y");
     // Format the footer as "Closes at <EXPIRATION_TIME>"
-    serenity::CreateEmbedFooter::new(format!("Closes at {formatted_expiration_time}"))
+    format!("Closes at {formatted_expiration_time}")
 }
 
 struct Poll {
@@ -179,37 +187,59 @@ struct Poll {
     vote_totals: Vec<u32>,
 }
 
+impl Poll {
+    fn embed_fields(&self, show_votes: bool) -> impl Iterator<Item = (String, String, bool)> + '_ {
+        self.options
+            .iter()
+            .cloned()
+            .zip(&self.vote_totals)
+            .map(move |(option, &votes)| {
+                let value = if show_votes {
+                    votes.to_string()
+                } else {
+                    String::new()
+                };
+                (option, value, false)
+            })
+    }
+}
+
 impl Interactive for Poll {
+    const KIND: &'static str = "poll";
+
+    fn permission(&self, custom_id: &str) -> Permission {
+        if custom_id.ends_with("_close") || custom_id.contains("_edit") {
+            Permission::Author
+        } else {
+            Permission::Everyone
+        }
+    }
+
     async fn process(
         &mut self,
-        ctx: ApplicationContext<'_>,
-        interaction: &serenity::ComponentInteraction,
+        ctx: &SessionContext<'_>,
+        interaction: &Interaction,
     ) -> Result<ControlFlow> {
-        let custom_id = &interaction.data.custom_id;
-        let user_id = interaction.user.id;
-        let new_votes: Vec<usize> = match &interaction.data.kind {
-            serenity::ComponentInteractionDataKind::Button if custom_id.ends_with("_close") => {
-                return if user_id == ctx.author().id {
-                    Ok(ControlFlow::Break)
-                } else {
-                    respond_to_interaction!(
-                        ctx,
-                        interaction,
-                        ":x: Only the person who started the poll can do this."
-                    )
-                    .await?;
-                    Ok(ControlFlow::Continue { update: false })
-                };
+        let custom_id = &interaction.custom_id;
+        let user_id = interaction.user_id;
+        let new_votes: Vec<usize> = match &interaction.kind {
+            InteractionKind::Button if custom_id.ends_with("_close") => {
+                return Ok(ControlFlow::Break);
             }
-            serenity::ComponentInteractionDataKind::Button if custom_id.ends_with("_clear") => {
-                Vec::new()
+            InteractionKind::Button if custom_id.ends_with("_edit") => {
+                let defaults = PollEditModal {
+                    title: self.title.clone(),
+                };
+                let modal_custom_id = format!("{}_poll_edit_modal", ctx.id());
+                return Ok(ControlFlow::modal(modal_custom_id, Some(defaults)));
             }
-            serenity::ComponentInteractionDataKind::Button => {
+            InteractionKind::Button if custom_id.ends_with("_clear") => Vec::new(),
+            InteractionKind::Button => {
                 let (_, option_number) = custom_id.rsplit_once('_').unwrap();
                 let vote = option_number.parse().unwrap();
                 vec![vote]
             }
-            serenity::ComponentInteractionDataKind::StringSelect { values } => {
+            InteractionKind::Select { values } => {
                 values.iter().map(|s| s.parse().unwrap()).collect()
             }
             _ => unreachable!(),
@@ -229,40 +259,55 @@ impl Interactive for Poll {
         })
     }
 
-    fn update(&mut self, _ctx: ApplicationContext<'_>, message: &mut InteractiveMessage) {
-        let fields = self
-            .options
-            .iter()
-            .cloned()
-            .zip(&self.vote_totals)
-            .map(|(option, &votes)| (option, votes.to_string(), false));
+    async fn process_modal(
+        &mut self,
+        _ctx: &SessionContext<'_>,
+        interaction: &Interaction,
+    ) -> Result<ControlFlow> {
+        let InteractionKind::Modal(data) = &interaction.kind else {
+            unreachable!()
+        };
+        match <PollEditModal as poise::Modal>::parse(data.clone()) {
+            Ok(PollEditModal { title }) => {
+                self.title = title;
+                Ok(ControlFlow::Continue { update: true })
+            }
+            Err(err) => Ok(ControlFlow::ephemeral(format!(":x: {err}"))),
+        }
+    }
+
+    fn update(&mut self, _ctx: &SessionContext<'_>, message: &mut InteractiveMessage) {
         let embed = self
             .embed_template
             .clone()
             .title(self.title.clone())
-            .fields(fields);
+            .fields(self.embed_fields(self.public_voting));
         message.modify_embed(|_| embed);
     }
 
     async fn finish(
         &mut self,
-        _ctx: ApplicationContext<'_>,
+        _ctx: &SessionContext<'_>,
         message: &mut InteractiveMessage,
+        reason: EndReason,
     ) -> Result<()> {
-        let fields = self
-            .options
-            .iter()
-            .cloned()
-            .zip(&self.vote_totals)
-            .map(|(option, &votes)| (option, votes.to_string(), false));
+        let title_prefix = match reason {
+            EndReason::Cancelled => "CANCELLED",
+            EndReason::Completed | EndReason::Expired | EndReason::Shutdown => "CLOSED",
+        };
         let embed = self
             .embed_template
             .clone()
-            .title(format!("[CLOSED] {}", self.title))
-            .fields(fields);
+            .title(format!("[{title_prefix}] {}", self.title))
+            .fields(self.embed_fields(true));
         message
             .modify_embed(|_| embed)
             .modify_action_rows(|_| Vec::new());
+        if reason == EndReason::Shutdown {
+            message.content = Some(String::from(
+                "⏸️ This poll was closed early because Patbot shut down.",
+            ));
+        }
         Ok(())
     }
 }
@@ -345,12 +390,17 @@ fn create_poll_action_rows(
         .emoji("✖️".parse::<serenity::ReactionType>().unwrap())
         .label("Clear my votes")
         .style(serenity::ButtonStyle::Primary);
+    let edit_poll_button = serenity::CreateButton::new(custom_id(id, "edit", None))
+        .emoji("✏️".parse::<serenity::ReactionType>().unwrap())
+        .label("Edit poll")
+        .style(serenity::ButtonStyle::Secondary);
     let close_poll_button = serenity::CreateButton::new(custom_id(id, "close", None))
         .emoji("🛑".parse::<serenity::ReactionType>().unwrap())
         .label("Close poll")
         .style(serenity::ButtonStyle::Danger);
     rows.push(serenity::CreateActionRow::Buttons(vec![
         clear_votes_button,
+        edit_poll_button,
         close_poll_button,
     ]));
 
@@ -380,6 +430,15 @@ struct PollModal {
     num_choices: Option<String>,
 }
 
+#[derive(poise::Modal, Debug)]
+#[name = "Edit poll"]
+struct PollEditModal {
+    #[name = "Title"]
+    #[placeholder = "Enter poll title here"]
+    #[max_length = 245]
+    title: String,
+}
+
 #[derive(FromStr, Copy, Clone)]
 #[display("{amount} {unit}")]
 struct PollDuration {
@@ -428,3 +487,53 @@ enum PollDurationUnit {
     #[from_str(regex = "days?")]
     Day,
 }
+
+#[tokio::test]
+async fn test_poll_session() {
+    use crate::interactive::fake::{self, FakeGateway};
+
+    let data = fake::data();
+    let ctx = fake::session_context(&data, 1);
+    let mut poll = Poll {
+        title: String::from("Lunch"),
+        options: vec![String::from("Pizza"), String::from("Tacos")],
+        public_voting: true,
+        embed_template: serenity::CreateEmbed::default(),
+        votes: HashMap::default(),
+        vote_totals: vec![0; 2],
+    };
+
+    let mut gateway = FakeGateway::new();
+    gateway
+        .click(2, "1_poll_choice_0")
+        .click(3, "1_poll_choice_1")
+        .select(4, "1_poll_menu", &["1"])
+        .click(2, "1_poll_choice_1")
+        .click(5, "1_poll_choice_0")
+        .click(5, "1_poll_clear")
+        // Only the author can close the poll
+        .click(2, "1_poll_close")
+        .click(1, "1_poll_close")
+        .click(6, "1_poll_choice_0");
+
+    let message = InteractiveMessage::new()
+        .embed(serenity::CreateEmbed::default())
+        .action_rows(Vec::new());
+    let config = InteractiveConfig {
+        duration: std::time::Duration::from_secs(60),
+        update_interval: std::time::Duration::ZERO,
+    };
+    let ended = message
+        .run_with(&ctx, &mut gateway, config, &mut poll)
+        .await
+        .unwrap();
+
+    assert_eq!(ended.reason, EndReason::Completed);
+    assert_eq!(gateway.ephemerals(), 1);
+    assert_eq!(poll.vote_totals, [0, 3]);
+
+    let embed = gateway.embed();
+    assert_eq!(embed["title"], "[CLOSED] Lunch");
+    assert_eq!(embed["fields"][0]["value"], "0");
+    assert_eq!(embed["fields"][1]["value"], "3");
+}
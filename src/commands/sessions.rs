@@ -0,0 +1,52 @@
+use crate::{interactive::Paginator, prelude::*};
+use serenity::Mentionable;
+
+#[poise::command(
+    slash_command,
+    owners_only,
+    subcommand_required,
+    subcommands("list", "cancel")
+)]
+pub async fn sessions(_ctx: ApplicationContext<'_>) -> Result {
+    unreachable!()
+}
+
+/// List every running poll, petition and other interactive session
+#[poise::command(slash_command, owners_only, ephemeral)]
+pub async fn list(ctx: ApplicationContext<'_>) -> Result {
+    const SESSIONS_PER_PAGE: usize = 10;
+
+    let sessions = ctx.data().sessions().list();
+    if sessions.is_empty() {
+        ctx.reply("There are no running sessions.").await?;
+        return Ok(());
+    }
+
+    let lines = sessions.into_iter().map(|session| {
+        format!(
+            "`#{}` {} started by {} in {}, expires <t:{}:R>",
+            session.id,
+            session.kind,
+            session.author.mention(),
+            session.channel.mention(),
+            session.expires_at.unix_timestamp()
+        )
+    });
+    Paginator::from_lines(ctx, "Running Sessions", lines, SESSIONS_PER_PAGE)
+        .send(ctx)
+        .await
+}
+
+/// Cancel a running session
+#[poise::command(slash_command, owners_only, ephemeral)]
+pub async fn cancel(
+    ctx: ApplicationContext<'_>,
+    #[description = "The number of the session, as shown in `/sessions list`"] id: u32,
+) -> Result {
+    if !ctx.data().sessions().cancel(id) {
+        reply_error!(ctx, "There is no running session `#{}`.", id);
+    }
+    ctx.reply(format!(":white_check_mark: Cancelled session `#{id}`."))
+        .await?;
+    Ok(())
+}
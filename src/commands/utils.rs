@@ -12,6 +12,9 @@ pub async fn ping(ctx: Context<'_>) -> Result {
 pub async fn quit(ctx: Context<'_>) -> Result {
     let _ = ctx.reply("Shutting down!").await;
 
+    // Close any open polls, petitions, etc. so their messages don't keep buttons that no longer work
+    ctx.data().sessions().shutdown_all().await;
+
     let shard_manager = ctx.framework().shard_manager();
     shard_manager.shutdown_all().await;
 
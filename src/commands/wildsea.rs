@@ -0,0 +1,470 @@
+use super::dice::{fair_roll, is_game_master, FairRollKind, Rolled};
+use crate::{data::Track, dice::seeded, prelude::*};
+use parse_display::FromStr;
+use poise::ChoiceParameter;
+use rand::Rng;
+use std::fmt;
+
+#[derive(FromStr, Debug, Copy, Clone, Eq, PartialEq)]
+enum WildseaRoll {
+    #[from_str(regex = r"(?P<dice>\d+)(?:d6)?\s*cut\s*(?P<cut>\d+)")]
+    Cut { dice: u32, cut: u32 },
+    #[from_str(regex = r"(?P<dice>\d+)(?:d6)?")]
+    NoCut { dice: u32 },
+}
+
+impl WildseaRoll {
+    pub fn into_parts(self) -> (u32, u32) {
+        match self {
+            Self::Cut { dice, cut } => (dice, cut),
+            Self::NoCut { dice } => (dice, 0),
+        }
+    }
+}
+
+/// Rolls and tracks for The Wildsea
+#[poise::command(
+    slash_command,
+    rename = "wildsea",
+    subcommand_required,
+    subcommands("roll_pool", "set_track", "mark_track", "show_tracks")
+)]
+pub async fn roll_wildsea(_ctx: Context<'_>) -> Result {
+    unreachable!()
+}
+
+#[derive(poise::ChoiceParameter, Default, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum WildseaRollKind {
+    #[default]
+    Action,
+    Attack,
+    Defense,
+    Acquisition,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Outcome {
+    Triumph,
+    Conflict,
+    Disaster,
+}
+
+impl Outcome {
+    fn from_highest(highest: u32) -> Self {
+        match highest {
+            6 => Self::Triumph,
+            4..=5 => Self::Conflict,
+            _ => Self::Disaster,
+        }
+    }
+
+    fn color(self) -> serenity::Color {
+        match self {
+            Self::Triumph => serenity::Color::DARK_GREEN,
+            Self::Conflict => serenity::Color::GOLD,
+            Self::Disaster => serenity::Color::RED,
+        }
+    }
+
+    fn meaning(self, kind: WildseaRollKind) -> &'static str {
+        match (kind, self) {
+            (WildseaRollKind::Action, Self::Triumph) => "You succeed without any complications.",
+            (WildseaRollKind::Action, Self::Conflict) => {
+                "You succeed, but there's a cost or a complication."
+            }
+            (WildseaRollKind::Action, Self::Disaster) => "You fail, and things get worse.",
+            (WildseaRollKind::Attack, Self::Triumph) => "You hit, dealing your full damage.",
+            (WildseaRollKind::Attack, Self::Conflict) => {
+                "You hit, but take damage in return or only deal some of yours."
+            }
+            (WildseaRollKind::Attack, Self::Disaster) => "You miss, and take damage.",
+            (WildseaRollKind::Defense, Self::Triumph) => "You avoid the harm entirely.",
+            (WildseaRollKind::Defense, Self::Conflict) => {
+                "You take some of the harm, or avoid it at a cost."
+            }
+            (WildseaRollKind::Defense, Self::Disaster) => "You take the full harm.",
+            (WildseaRollKind::Acquisition, Self::Triumph) => {
+                "You gain the resource you were after, and maybe something more."
+            }
+            (WildseaRollKind::Acquisition, Self::Conflict) => {
+                "You gain a resource, but it isn't quite what you were after."
+            }
+            (WildseaRollKind::Acquisition, Self::Disaster) => {
+                "You gain nothing useful, or something dangerous."
+            }
+        }
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Triumph => "Triumph",
+            Self::Conflict => "Conflict",
+            Self::Disaster => "Disaster",
+        })
+    }
+}
+
+/// A pool of d6s with the highest `cut` dice removed
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct WildseaResult {
+    /// From highest to lowest
+    rolls: Vec<u32>,
+    cut: usize,
+}
+
+impl WildseaResult {
+    pub fn roll(dice: u32, cut: u32, rng: &mut impl Rng) -> Self {
+        let mut rolls: Vec<_> = std::iter::repeat_with(|| seeded::die(rng, 6))
+            .take(dice as usize)
+            .collect();
+        rolls.sort_unstable_by(|a, b| b.cmp(a));
+        Self {
+            rolls,
+            cut: cut as usize,
+        }
+    }
+
+    pub fn rolls(&self) -> &[u32] {
+        &self.rolls
+    }
+
+    fn kept(&self) -> &[u32] {
+        &self.rolls[self.cut..]
+    }
+
+    fn highest(&self) -> u32 {
+        self.kept()[0]
+    }
+
+    fn outcome(&self) -> Outcome {
+        Outcome::from_highest(self.highest())
+    }
+
+    /// Rolling doubles among the kept dice adds a twist to the outcome
+    fn twist(&self) -> bool {
+        self.kept().windows(2).any(|w| w[0] == w[1])
+    }
+
+    /// The dice from highest to lowest, with the cut dice struck out and the highest kept die
+    /// in bold
+    fn dice_text(&self) -> String {
+        self.rolls
+            .iter()
+            .enumerate()
+            .map(|(i, roll)| match i.cmp(&self.cut) {
+                std::cmp::Ordering::Less => format!("~~{roll}~~"),
+                std::cmp::Ordering::Equal => format!("**{roll}**"),
+                std::cmp::Ordering::Greater => roll.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn title(&self) -> String {
+        if self.twist() {
+            format!("{}... with a twist!", self.outcome())
+        } else {
+            format!("{}!", self.outcome())
+        }
+    }
+}
+
+impl fmt::Display for WildseaResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} → {}", self.dice_text(), self.title())
+    }
+}
+
+/// Rolls a pool of d6s, cutting the highest
+#[poise::command(slash_command, rename = "roll")]
+pub async fn roll_pool(
+    ctx: Context<'_>,
+    #[description = "The dice to roll. Use `X cut Y` format, or `X` if no cuts."] dice: String,
+    #[description = "What the roll is for, which decides what the outcome means"] kind: Option<
+        WildseaRollKind,
+    >,
+) -> Result {
+    let Ok(dice) = dice.trim().to_ascii_lowercase().parse::<WildseaRoll>() else {
+        reply_error!(
+            ctx,
+            "Error: please use the format `X cut Y`, or `X` if no cuts."
+        );
+    };
+    let (dice, cut) = dice.into_parts();
+
+    if dice == 0 || dice > 20 {
+        reply_error!(ctx, "Error: you can roll 1 to 20 dice.");
+    }
+    if cut >= dice {
+        reply_error!(
+            ctx,
+            "Error: the number of dice to cut must be less than the number of dice to roll."
+        );
+    }
+
+    let kind = kind.unwrap_or_default();
+    let (id, Rolled::Wildsea(result)) = fair_roll(
+        ctx.data(),
+        ctx.author().id,
+        FairRollKind::Wildsea { dice, cut },
+        false,
+    ) else {
+        unreachable!()
+    };
+
+    let outcome = result.outcome();
+    let mut embed = serenity::CreateEmbed::default()
+        .color(outcome.color())
+        .title(result.title())
+        .description(outcome.meaning(kind))
+        .field("Dice", result.dice_text(), true)
+        .field("Roll", kind.name(), true)
+        .footer(serenity::CreateEmbedFooter::new(format!("Roll #{id}")));
+    if result.twist() {
+        embed = embed.field(
+            "Twist",
+            "You rolled doubles, so something unexpected happens too. Anyone at the table can \
+            suggest what.",
+            false,
+        );
+    }
+
+    ctx.send(poise::CreateReply::default().embed(embed).reply(true))
+        .await?;
+    Ok(())
+}
+
+#[derive(
+    poise::ChoiceParameter, Debug, Copy, Clone, Eq, PartialEq, serde::Serialize, serde::Deserialize,
+)]
+pub enum TrackOwner {
+    Ship,
+    Character,
+}
+
+/// Whether the author can change the sheet: whoever made it, or the channel's GM. Anyone can make
+/// a new sheet.
+async fn can_change_sheet(ctx: Context<'_>, owner: TrackOwner, name: &str) -> bool {
+    let guild_id = ctx.guild_id().unwrap();
+    let creator = ctx
+        .data()
+        .use_wildsea_tracks(|tracks| Some(tracks.get(guild_id, owner, name)?.creator));
+    if creator.is_none_or(|creator| creator == ctx.author().id.get()) {
+        return true;
+    }
+    let game_master = ctx.data().use_game_masters(|gms| gms.get(ctx.channel_id()));
+    match game_master {
+        Some(game_master) => is_game_master(ctx, game_master).await,
+        None => false,
+    }
+}
+
+/// Adds a track like `hull`, `crew` or `mire` to a ship or character, or resizes it
+#[poise::command(slash_command, guild_only, rename = "track")]
+pub async fn set_track(
+    ctx: Context<'_>,
+    #[description = "Whether the track belongs to a ship or a character"] owner: TrackOwner,
+    #[description = "The ship or character's name"]
+    #[autocomplete = "autocomplete_name"]
+    name: String,
+    #[description = "The name of the track"] track: String,
+    #[description = "How many boxes the track has. Use 0 to remove it"] size: u32,
+) -> Result {
+    const MAX_SIZE: u32 = 20;
+
+    let name = name.trim();
+    let track = track.trim();
+    if name.is_empty() || track.is_empty() {
+        reply_error!(ctx, "The name and track can't be empty.");
+    }
+    if size > MAX_SIZE {
+        reply_error!(ctx, "Tracks can have at most {} boxes.", MAX_SIZE);
+    }
+    if !can_change_sheet(ctx, owner, name).await {
+        reply_error!(
+            ctx,
+            "Only whoever made {}'s tracks or the channel's GM can change them.",
+            name
+        );
+    }
+
+    let guild_id = ctx.guild_id().unwrap();
+    let sheet_exists = ctx.data().use_wildsea_tracks_mut(|tracks| {
+        tracks.set_track(guild_id, ctx.author().id, owner, name, track, size);
+        tracks.get(guild_id, owner, name).is_some()
+    });
+    if size > 0 {
+        return show_sheet(ctx, owner, name, None).await;
+    }
+
+    let track = track.to_ascii_lowercase();
+    if sheet_exists {
+        let note = format!("Removed the {track} track.");
+        show_sheet(ctx, owner, name, Some(note)).await
+    } else {
+        ctx.reply(format!(
+            "Removed the {track} track, and {name} has no tracks left."
+        ))
+        .await?;
+        Ok(())
+    }
+}
+
+/// Marks boxes on a ship or character's track, or clears them
+#[poise::command(slash_command, guild_only, rename = "mark")]
+pub async fn mark_track(
+    ctx: Context<'_>,
+    #[description = "Whether the track belongs to a ship or a character"] owner: TrackOwner,
+    #[description = "The ship or character's name"]
+    #[autocomplete = "autocomplete_name"]
+    name: String,
+    #[description = "The name of the track"] track: String,
+    #[description = "How many boxes to mark. Use a negative number to clear them"] amount: i32,
+) -> Result {
+    let name = name.trim();
+    let track = track.trim();
+    let guild_id = ctx.guild_id().unwrap();
+    let has_track = ctx.data().use_wildsea_tracks(|tracks| {
+        tracks
+            .get(guild_id, owner, name)
+            .is_some_and(|sheet| sheet.tracks.contains_key(&track.to_ascii_lowercase()))
+    });
+    if !has_track {
+        reply_error!(ctx, "{} doesn't have a `{}` track.", name, track);
+    }
+    if !can_change_sheet(ctx, owner, name).await {
+        reply_error!(
+            ctx,
+            "Only whoever made {}'s tracks or the channel's GM can change them.",
+            name
+        );
+    }
+
+    let marked = ctx
+        .data()
+        .use_wildsea_tracks_mut(|tracks| tracks.mark(guild_id, owner, name, track, amount));
+    let Some(marked) = marked else {
+        reply_error!(ctx, "{} doesn't have a `{}` track.", name, track);
+    };
+
+    let note = (marked.marked == marked.size && amount > 0)
+        .then(|| format!("⚠️ The {} track is full!", track.to_ascii_lowercase()));
+    show_sheet(ctx, owner, name, note).await
+}
+
+/// Shows a ship or character's tracks
+#[poise::command(slash_command, guild_only, rename = "tracks")]
+pub async fn show_tracks(
+    ctx: Context<'_>,
+    #[description = "Whether the tracks belong to a ship or a character"] owner: TrackOwner,
+    #[description = "The ship or character's name"]
+    #[autocomplete = "autocomplete_name"]
+    name: String,
+) -> Result {
+    show_sheet(ctx, owner, name.trim(), None).await
+}
+
+async fn show_sheet(
+    ctx: Context<'_>,
+    owner: TrackOwner,
+    name: &str,
+    note: Option<String>,
+) -> Result {
+    let guild_id = ctx.guild_id().unwrap();
+    let sheet = ctx
+        .data()
+        .use_wildsea_tracks(|tracks| tracks.get(guild_id, owner, name).cloned());
+    let Some(sheet) = sheet else {
+        reply_error!(ctx, "{} doesn't have any tracks.", name);
+    };
+
+    let fields = sheet
+        .tracks
+        .iter()
+        .map(|(track, &value)| (capitalize(track), track_boxes(value), false));
+    let mut embed = serenity::CreateEmbed::default()
+        .color(ctx.data().bot_color())
+        .title(format!("{} ({})", sheet.name, owner.name()))
+        .fields(fields);
+    if let Some(note) = note {
+        embed = embed.description(note);
+    }
+    ctx.send(poise::CreateReply::default().embed(embed).reply(true))
+        .await?;
+    Ok(())
+}
+
+/// Shows a track like `■■□□ 2/4`
+fn track_boxes(track: Track) -> String {
+    let marked = "■".repeat(track.marked as usize);
+    let unmarked = "□".repeat((track.size - track.marked) as usize);
+    format!("{marked}{unmarked} {}/{}", track.marked, track.size)
+}
+
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+async fn autocomplete_name<'a>(
+    ctx: Context<'_>,
+    partial: &'a str,
+) -> impl Iterator<Item = String> + 'a {
+    let guild_id = ctx.guild_id();
+    let mut names: Vec<String> = ctx.data().use_wildsea_tracks(|tracks| {
+        let Some(guild_id) = guild_id else {
+            return Vec::new();
+        };
+        [TrackOwner::Ship, TrackOwner::Character]
+            .into_iter()
+            .flat_map(|owner| {
+                let names = tracks.names(guild_id, owner).map(str::to_owned);
+                names.collect::<Vec<_>>()
+            })
+            .collect()
+    });
+    names.sort_unstable();
+    names.dedup();
+    let partial = partial.to_ascii_lowercase();
+    names
+        .into_iter()
+        .filter(move |name| name.to_ascii_lowercase().starts_with(&partial))
+}
+
+#[test]
+fn test_parse_wildsea_dice() {
+    assert_eq!(
+        "3d6".parse::<WildseaRoll>().unwrap(),
+        WildseaRoll::NoCut { dice: 3 }
+    );
+    assert_eq!(
+        "3d6 cut 1".parse::<WildseaRoll>().unwrap(),
+        WildseaRoll::Cut { dice: 3, cut: 1 }
+    );
+}
+
+#[test]
+fn test_wildsea_result() {
+    let result = WildseaResult {
+        rolls: vec![6, 5, 5, 2],
+        cut: 1,
+    };
+    assert_eq!(result.outcome(), Outcome::Conflict);
+    assert!(result.twist());
+    assert_eq!(
+        result.to_string(),
+        "~~6~~ **5** 5 2 → Conflict... with a twist!"
+    );
+
+    let result = WildseaResult {
+        rolls: vec![6, 6, 1],
+        cut: 2,
+    };
+    assert_eq!(result.outcome(), Outcome::Disaster);
+    assert!(!result.twist());
+}
@@ -1,21 +1,35 @@
 use crate::{
-    commands::{PollMode, ReplyCommand, ReplyCommandResponse},
+    commands::{
+        FairRoll, PetitionRecord, PollMode, ReplyCommand, ReplyCommandResponse, RollRecord,
+        TrackOwner, VoteChange,
+    },
+    dice::seeded::{self, Seed},
+    interactive::Sessions,
     prelude::*,
 };
 use serde::{Deserialize, Serialize};
 use serenity::Color;
 use shuttle_persist::PersistInstance;
-use std::{collections::HashMap, fmt, sync::Mutex};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+    sync::Mutex,
+};
 use time::{Duration, OffsetDateTime};
 
 pub struct Data {
     bot_color: Color,
     poll_mode: Mutex<PollMode>,
+    sessions: Sessions,
     writer: DataWriter,
 }
 
 mod writer {
-    use super::{Buckets, Counters, ReplyCommands};
+    use super::{
+        Buckets, Characters, Counters, Delegations, DiceMacros, FairRoll, FairRollLog, Fairness,
+        GameMasters, Petitions, ReplyCommands, RollHistory, WildseaTracks,
+    };
+    use serde::de::DeserializeOwned;
     use shuttle_persist::PersistInstance;
     use std::sync::Mutex;
 
@@ -23,21 +37,40 @@ mod writer {
         persist: PersistInstance,
         buckets: Mutex<Buckets>,
         counters: Mutex<Counters>,
+        delegations: Mutex<Delegations>,
+        petitions: Mutex<Petitions>,
         reply_commands: Mutex<ReplyCommands>,
+        rolls: Mutex<RollHistory>,
+        dice_macros: Mutex<DiceMacros>,
+        characters: Mutex<Characters>,
+        game_masters: Mutex<GameMasters>,
+        fairness: Mutex<Fairness>,
+        fair_roll_log: Mutex<FairRollLog>,
+        wildsea_tracks: Mutex<WildseaTracks>,
     }
 
     impl DataWriter {
-        pub fn new(
-            persist: PersistInstance,
-            buckets: Mutex<Buckets>,
-            counters: Mutex<Counters>,
-            reply_commands: Mutex<ReplyCommands>,
-        ) -> Self {
+        /// Loads each store from `persist`, or its default if it was never saved
+        pub fn load(persist: PersistInstance) -> Self {
+            let reply_commands = load::<ReplyCommands>(&persist, "reply_commands");
+            reply_commands.lock().unwrap().clear_ids();
+            let fairness: Fairness = persist.load("fairness").unwrap_or_default();
+            let fair_roll_log = Mutex::new(FairRollLog::load(&persist, fairness.next_nonce));
+
             Self {
-                persist,
-                buckets,
-                counters,
+                buckets: load(&persist, "buckets"),
+                counters: load(&persist, "counters"),
+                delegations: load(&persist, "delegations"),
+                petitions: load(&persist, "petitions"),
                 reply_commands,
+                rolls: load(&persist, "rolls"),
+                dice_macros: load(&persist, "dice_macros"),
+                characters: load(&persist, "characters"),
+                game_masters: load(&persist, "game_masters"),
+                fairness: Mutex::new(fairness),
+                fair_roll_log,
+                wildsea_tracks: load(&persist, "wildsea_tracks"),
+                persist,
             }
         }
 
@@ -83,6 +116,48 @@ mod writer {
             ret
         }
 
+        pub fn use_delegations<F, T>(&self, f: F) -> T
+        where
+            F: FnOnce(&Delegations) -> T,
+        {
+            let delegations = self.delegations.lock().unwrap();
+            f(&delegations)
+        }
+
+        pub fn use_delegations_mut<F, T>(&self, f: F) -> T
+        where
+            F: FnOnce(&mut Delegations) -> T,
+        {
+            let (ret, delegations_clone) = {
+                let mut delegations = self.delegations.lock().unwrap();
+                let ret = f(&mut delegations);
+                (ret, delegations.clone())
+            };
+            self.persist.save("delegations", delegations_clone).unwrap();
+            ret
+        }
+
+        pub fn use_petitions<F, T>(&self, f: F) -> T
+        where
+            F: FnOnce(&Petitions) -> T,
+        {
+            let petitions = self.petitions.lock().unwrap();
+            f(&petitions)
+        }
+
+        pub fn use_petitions_mut<F, T>(&self, f: F) -> T
+        where
+            F: FnOnce(&mut Petitions) -> T,
+        {
+            let (ret, petitions_clone) = {
+                let mut petitions = self.petitions.lock().unwrap();
+                let ret = f(&mut petitions);
+                (ret, petitions.clone())
+            };
+            self.persist.save("petitions", petitions_clone).unwrap();
+            ret
+        }
+
         pub fn use_reply_commands<F, T>(&self, f: F) -> T
         where
             F: FnOnce(&ReplyCommands) -> T,
@@ -105,6 +180,162 @@ mod writer {
                 .unwrap();
             ret
         }
+
+        pub fn use_rolls<F, T>(&self, f: F) -> T
+        where
+            F: FnOnce(&RollHistory) -> T,
+        {
+            let rolls = self.rolls.lock().unwrap();
+            f(&rolls)
+        }
+
+        pub fn use_rolls_mut<F, T>(&self, f: F) -> T
+        where
+            F: FnOnce(&mut RollHistory) -> T,
+        {
+            let (ret, rolls_clone) = {
+                let mut rolls = self.rolls.lock().unwrap();
+                let ret = f(&mut rolls);
+                (ret, rolls.clone())
+            };
+            self.persist.save("rolls", rolls_clone).unwrap();
+            ret
+        }
+
+        pub fn use_dice_macros<F, T>(&self, f: F) -> T
+        where
+            F: FnOnce(&DiceMacros) -> T,
+        {
+            let dice_macros = self.dice_macros.lock().unwrap();
+            f(&dice_macros)
+        }
+
+        pub fn use_dice_macros_mut<F, T>(&self, f: F) -> T
+        where
+            F: FnOnce(&mut DiceMacros) -> T,
+        {
+            let (ret, dice_macros_clone) = {
+                let mut dice_macros = self.dice_macros.lock().unwrap();
+                let ret = f(&mut dice_macros);
+                (ret, dice_macros.clone())
+            };
+            self.persist.save("dice_macros", dice_macros_clone).unwrap();
+            ret
+        }
+
+        pub fn use_characters<F, T>(&self, f: F) -> T
+        where
+            F: FnOnce(&Characters) -> T,
+        {
+            let characters = self.characters.lock().unwrap();
+            f(&characters)
+        }
+
+        pub fn use_characters_mut<F, T>(&self, f: F) -> T
+        where
+            F: FnOnce(&mut Characters) -> T,
+        {
+            let (ret, characters_clone) = {
+                let mut characters = self.characters.lock().unwrap();
+                let ret = f(&mut characters);
+                (ret, characters.clone())
+            };
+            self.persist.save("characters", characters_clone).unwrap();
+            ret
+        }
+
+        pub fn use_game_masters<F, T>(&self, f: F) -> T
+        where
+            F: FnOnce(&GameMasters) -> T,
+        {
+            let game_masters = self.game_masters.lock().unwrap();
+            f(&game_masters)
+        }
+
+        pub fn use_game_masters_mut<F, T>(&self, f: F) -> T
+        where
+            F: FnOnce(&mut GameMasters) -> T,
+        {
+            let (ret, game_masters_clone) = {
+                let mut game_masters = self.game_masters.lock().unwrap();
+                let ret = f(&mut game_masters);
+                (ret, game_masters.clone())
+            };
+            self.persist
+                .save("game_masters", game_masters_clone)
+                .unwrap();
+            ret
+        }
+
+        pub fn use_fairness<F, T>(&self, f: F) -> T
+        where
+            F: FnOnce(&Fairness) -> T,
+        {
+            let fairness = self.fairness.lock().unwrap();
+            f(&fairness)
+        }
+
+        pub fn use_fairness_mut<F, T>(&self, f: F) -> T
+        where
+            F: FnOnce(&mut Fairness) -> T,
+        {
+            let (ret, fairness_clone) = {
+                let mut fairness = self.fairness.lock().unwrap();
+                let ret = f(&mut fairness);
+                (ret, fairness.clone())
+            };
+            self.persist.save("fairness", fairness_clone).unwrap();
+            ret
+        }
+
+        pub fn use_fair_roll_log<F, T>(&self, f: F) -> T
+        where
+            F: FnOnce(&FairRollLog) -> T,
+        {
+            let fair_roll_log = self.fair_roll_log.lock().unwrap();
+            f(&fair_roll_log)
+        }
+
+        /// The log is too big to save on every roll, so this only saves the chunk that the roll
+        /// was added to, and removes the chunks that were dropped
+        pub fn log_fair_roll(&self, roll: FairRoll) {
+            let (index, chunk, dropped) = {
+                let mut fair_roll_log = self.fair_roll_log.lock().unwrap();
+                let (index, dropped) = fair_roll_log.push(roll);
+                (index, fair_roll_log.chunk(index).to_vec(), dropped)
+            };
+            self.persist.save(&FairRollLog::key(index), chunk).unwrap();
+            for index in dropped {
+                let _ = self.persist.remove(&FairRollLog::key(index));
+            }
+        }
+
+        pub fn use_wildsea_tracks<F, T>(&self, f: F) -> T
+        where
+            F: FnOnce(&WildseaTracks) -> T,
+        {
+            let wildsea_tracks = self.wildsea_tracks.lock().unwrap();
+            f(&wildsea_tracks)
+        }
+
+        pub fn use_wildsea_tracks_mut<F, T>(&self, f: F) -> T
+        where
+            F: FnOnce(&mut WildseaTracks) -> T,
+        {
+            let (ret, wildsea_tracks_clone) = {
+                let mut wildsea_tracks = self.wildsea_tracks.lock().unwrap();
+                let ret = f(&mut wildsea_tracks);
+                (ret, wildsea_tracks.clone())
+            };
+            self.persist
+                .save("wildsea_tracks", wildsea_tracks_clone)
+                .unwrap();
+            ret
+        }
+    }
+
+    fn load<T: Default + DeserializeOwned>(persist: &PersistInstance, key: &str) -> Mutex<T> {
+        Mutex::new(persist.load(key).unwrap_or_default())
     }
 }
 
@@ -118,28 +349,17 @@ impl Data {
             .await?
             .accent_colour
             .unwrap_or(Color::BLURPLE);
+        Ok(Self::load(persist, bot_color))
+    }
 
-        let buckets = match persist.load::<Buckets>("buckets") {
-            Ok(x) => Mutex::new(x),
-            Err(_) => Default::default(),
-        };
-        let counters = match persist.load::<Counters>("counters") {
-            Ok(x) => Mutex::new(x),
-            Err(_) => Default::default(),
-        };
-        let reply_commands = match persist.load::<ReplyCommands>("reply_commands") {
-            Ok(mut x) => {
-                x.clear_ids();
-                Mutex::new(x)
-            }
-            Err(_) => Default::default(),
-        };
-
-        Ok(Self {
+    /// Loads everything that was saved to `persist`, falling back on defaults for anything missing
+    pub fn load(persist: PersistInstance, bot_color: Color) -> Self {
+        Self {
             bot_color,
             poll_mode: Default::default(),
-            writer: DataWriter::new(persist, buckets, counters, reply_commands),
-        })
+            sessions: Default::default(),
+            writer: DataWriter::load(persist),
+        }
     }
 
     pub fn bot_color(&self) -> Color {
@@ -154,6 +374,10 @@ impl Data {
         *self.poll_mode.lock().unwrap() = mode;
     }
 
+    pub fn sessions(&self) -> &Sessions {
+        &self.sessions
+    }
+
     pub async fn register_reply_command(
         &self,
         ctx: Context<'_>,
@@ -378,6 +602,735 @@ impl Counters {
     }
 }
 
+/// Petition vote delegations, keyed by the ID of the member who delegated their vote. IDs are
+/// u64 since serenity's IDs don't round-trip through `shuttle_persist`.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct Delegations {
+    inner: HashMap<u64, Delegation>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Delegation {
+    pub delegate: u64,
+    pub expires_at: OffsetDateTime,
+}
+
+impl Delegation {
+    pub fn is_active(&self) -> bool {
+        OffsetDateTime::now_utc() < self.expires_at
+    }
+}
+
+impl Delegations {
+    /// Returns the previous delegation, if it was still active
+    pub fn delegate(
+        &mut self,
+        from: serenity::UserId,
+        to: serenity::UserId,
+        expires_at: OffsetDateTime,
+    ) -> Option<Delegation> {
+        self.inner.retain(|_, delegation| delegation.is_active());
+        let delegation = Delegation {
+            delegate: to.get(),
+            expires_at,
+        };
+        self.inner.insert(from.get(), delegation)
+    }
+
+    /// Returns the revoked delegation, if it was still active
+    pub fn revoke(&mut self, from: serenity::UserId) -> Option<Delegation> {
+        self.inner.remove(&from.get()).filter(Delegation::is_active)
+    }
+
+    /// Maps each member with an active delegation to the member voting on their behalf
+    pub fn active(&self) -> HashMap<serenity::UserId, serenity::UserId> {
+        self.inner
+            .iter()
+            .filter(|(_, delegation)| delegation.is_active())
+            .map(|(&from, delegation)| {
+                (
+                    serenity::UserId::new(from),
+                    serenity::UserId::new(delegation.delegate),
+                )
+            })
+            .collect()
+    }
+}
+
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct Petitions {
+    records: Vec<PetitionRecord>,
+}
+
+impl Petitions {
+    /// Returns the ID of the new petition
+    pub fn create(&mut self, title: impl ToString, author: serenity::UserId) -> u32 {
+        let id = self.records.last().map_or(1, |record| record.id + 1);
+        self.records.push(PetitionRecord {
+            id,
+            title: title.to_string(),
+            author: author.get(),
+            created_at: OffsetDateTime::now_utc(),
+            audit_log: Vec::new(),
+        });
+        id
+    }
+
+    pub fn record_vote_change(&mut self, id: u32, change: VoteChange) {
+        if let Some(record) = self.records.iter_mut().find(|record| record.id == id) {
+            record.audit_log.push(change);
+        }
+    }
+
+    pub fn get(&self, id: u32) -> Option<&PetitionRecord> {
+        self.records.iter().find(|record| record.id == id)
+    }
+}
+
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct RollHistory {
+    records: Vec<RollRecord>,
+}
+
+impl RollHistory {
+    /// Only the most recent rolls are kept, since the whole history is saved after every roll
+    const MAX_RECORDS: usize = 10_000;
+
+    pub fn record(&mut self, record: RollRecord) {
+        if self.records.len() >= Self::MAX_RECORDS {
+            let excess = self.records.len() + 1 - Self::MAX_RECORDS;
+            self.records.drain(..excess);
+        }
+        self.records.push(record);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &RollRecord> {
+        self.records.iter()
+    }
+}
+
+/// Saved dice expressions, which can be rolled by name or referenced in other expressions as
+/// `@name`
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct DiceMacros {
+    users: HashMap<u64, BTreeMap<String, String>>,
+    /// Shared by everyone in the guild. Members' own macros take priority over these.
+    guilds: HashMap<u64, BTreeMap<String, String>>,
+    /// Who saved each guild macro, keyed the same way as `guilds`
+    #[serde(default)]
+    guild_creators: HashMap<u64, BTreeMap<String, u64>>,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MacroOwner {
+    User(serenity::UserId),
+    Guild(serenity::GuildId),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum MacroError {
+    Unknown(String),
+    /// The macro refers back to itself, directly or through other macros
+    Cycle(String),
+    /// The expression would be longer than `DiceMacros::MAX_EXPANDED_LEN` once expanded
+    TooLong,
+}
+
+impl std::error::Error for MacroError {}
+
+impl fmt::Display for MacroError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unknown(name) => write!(f, "there is no macro named `@{name}`"),
+            Self::Cycle(name) => write!(f, "`@{name}` refers to itself"),
+            Self::TooLong => write!(
+                f,
+                "it would be longer than {} characters once its macros are filled in",
+                DiceMacros::MAX_EXPANDED_LEN
+            ),
+        }
+    }
+}
+
+impl DiceMacros {
+    /// Macros can refer to each other many times over, so the expanded expression could otherwise
+    /// grow exponentially
+    pub const MAX_EXPANDED_LEN: usize = 1000;
+
+    fn owned(&self, owner: MacroOwner) -> Option<&BTreeMap<String, String>> {
+        match owner {
+            MacroOwner::User(user_id) => self.users.get(&user_id.get()),
+            MacroOwner::Guild(guild_id) => self.guilds.get(&guild_id.get()),
+        }
+    }
+
+    fn owned_mut(&mut self, owner: MacroOwner) -> &mut BTreeMap<String, String> {
+        match owner {
+            MacroOwner::User(user_id) => self.users.entry(user_id.get()).or_default(),
+            MacroOwner::Guild(guild_id) => self.guilds.entry(guild_id.get()).or_default(),
+        }
+    }
+
+    /// Saves the macro if everything it refers to exists. Returns the previous expression.
+    pub fn save(
+        &mut self,
+        owner: MacroOwner,
+        name: String,
+        expression: String,
+        user_id: serenity::UserId,
+        guild_id: Option<serenity::GuildId>,
+    ) -> Result<Option<String>, MacroError> {
+        let mut expanding = vec![name.clone()];
+        self.expand_inner(&expression, user_id, guild_id, &mut expanding)?;
+        if let MacroOwner::Guild(guild_id) = owner {
+            let creators = self.guild_creators.entry(guild_id.get()).or_default();
+            creators.entry(name.clone()).or_insert(user_id.get());
+        }
+        Ok(self.owned_mut(owner).insert(name, expression))
+    }
+
+    /// Returns false if the macro did not exist
+    pub fn delete(&mut self, owner: MacroOwner, name: &str) -> bool {
+        if let MacroOwner::Guild(guild_id) = owner {
+            if let Some(creators) = self.guild_creators.get_mut(&guild_id.get()) {
+                creators.remove(name);
+            }
+        }
+        self.owned_mut(owner).remove(name).is_some()
+    }
+
+    pub fn contains(&self, owner: MacroOwner, name: &str) -> bool {
+        self.owned(owner)
+            .is_some_and(|macros| macros.contains_key(name))
+    }
+
+    /// Who saved the guild macro. Macros saved before creators were recorded have none.
+    pub fn creator(&self, guild_id: serenity::GuildId, name: &str) -> Option<serenity::UserId> {
+        let creator = self.guild_creators.get(&guild_id.get())?.get(name)?;
+        Some(serenity::UserId::new(*creator))
+    }
+
+    pub fn list(&self, owner: MacroOwner) -> impl Iterator<Item = (&str, &str)> {
+        self.owned(owner)
+            .into_iter()
+            .flatten()
+            .map(|(name, expression)| (name.as_str(), expression.as_str()))
+    }
+
+    /// Looks up a macro the way a member sees it
+    pub fn get(
+        &self,
+        name: &str,
+        user_id: serenity::UserId,
+        guild_id: Option<serenity::GuildId>,
+    ) -> Option<&str> {
+        let owners =
+            std::iter::once(MacroOwner::User(user_id)).chain(guild_id.map(MacroOwner::Guild));
+        owners
+            .filter_map(|owner| self.owned(owner)?.get(name))
+            .map(String::as_str)
+            .next()
+    }
+
+    /// Every macro name the member can use
+    pub fn names(
+        &self,
+        user_id: serenity::UserId,
+        guild_id: Option<serenity::GuildId>,
+    ) -> BTreeSet<&str> {
+        let mut names: BTreeSet<_> = self
+            .list(MacroOwner::User(user_id))
+            .map(|(name, _)| name)
+            .collect();
+        if let Some(guild_id) = guild_id {
+            names.extend(self.list(MacroOwner::Guild(guild_id)).map(|(name, _)| name));
+        }
+        names
+    }
+
+    /// Replaces every `@name` in the expression with what it refers to. Macros that consist of
+    /// more than one term are wrapped in parentheses.
+    pub fn expand(
+        &self,
+        expression: &str,
+        user_id: serenity::UserId,
+        guild_id: Option<serenity::GuildId>,
+    ) -> Result<String, MacroError> {
+        let mut expanding = Vec::new();
+        self.expand_inner(expression, user_id, guild_id, &mut expanding)
+    }
+
+    fn expand_inner(
+        &self,
+        expression: &str,
+        user_id: serenity::UserId,
+        guild_id: Option<serenity::GuildId>,
+        expanding: &mut Vec<String>,
+    ) -> Result<String, MacroError> {
+        let mut expanded = String::with_capacity(expression.len());
+        let mut rest = expression;
+        while let Some(at) = rest.find('@') {
+            expanded.push_str(&rest[..at]);
+            let after = &rest[at + 1..];
+            let len = after
+                .find(|c: char| !is_name_char(c))
+                .unwrap_or(after.len());
+            let name = after[..len].to_ascii_lowercase();
+            rest = &after[len..];
+
+            if expanding.contains(&name) {
+                return Err(MacroError::Cycle(name));
+            }
+            let Some(body) = self.get(&name, user_id, guild_id) else {
+                return Err(MacroError::Unknown(name));
+            };
+            expanding.push(name);
+            let body = self.expand_inner(body, user_id, guild_id, expanding)?;
+            expanding.pop();
+
+            let body = body.trim();
+            if body.chars().skip(1).any(|c| c == '+' || c == '-') {
+                expanded.push('(');
+                expanded.push_str(body);
+                expanded.push(')');
+            } else {
+                expanded.push_str(body);
+            }
+            if expanded.len() > Self::MAX_EXPANDED_LEN {
+                return Err(MacroError::TooLong);
+            }
+        }
+        expanded.push_str(rest);
+        if expanded.len() > Self::MAX_EXPANDED_LEN {
+            return Err(MacroError::TooLong);
+        }
+        Ok(expanded)
+    }
+}
+
+/// Whether the character can be part of the name of a dice macro or character stat
+pub fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct Characters {
+    sheets: Vec<CharacterSheet>,
+    /// The campaign each member is currently playing, keyed by user ID. `$stat`s in dice
+    /// expressions refer to their character in that campaign.
+    active: HashMap<u64, String>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CharacterSheet {
+    pub owner: u64,
+    pub campaign: String,
+    pub name: String,
+    pub stats: BTreeMap<String, i64>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum StatError {
+    NoCharacter,
+    Unknown { stat: String, character: String },
+}
+
+impl std::error::Error for StatError {}
+
+impl fmt::Display for StatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoCharacter => f.write_str(
+                "you need a character to use `$stats`. Create one with `/character create`",
+            ),
+            Self::Unknown { stat, character } => {
+                write!(f, "{character} doesn't have a stat named `${stat}`")
+            }
+        }
+    }
+}
+
+impl CharacterSheet {
+    /// D&D ability modifiers, like `dex_mod`, are worked out from the ability score if they
+    /// weren't set themselves.
+    pub fn stat(&self, name: &str) -> Option<i64> {
+        if let Some(&value) = self.stats.get(name) {
+            return Some(value);
+        }
+        let ability = name.strip_suffix("_mod")?;
+        let score = self.stats.get(ability)?;
+        Some((score - 10).div_euclid(2))
+    }
+
+    fn is_in(&self, owner: serenity::UserId, campaign: &str) -> bool {
+        self.owner == owner.get() && self.campaign.eq_ignore_ascii_case(campaign)
+    }
+}
+
+impl Characters {
+    /// Creates the character and makes it the member's active one. Returns false if they
+    /// already have a character in the campaign.
+    pub fn create(&mut self, owner: serenity::UserId, campaign: &str, name: &str) -> bool {
+        if self.sheets.iter().any(|sheet| sheet.is_in(owner, campaign)) {
+            return false;
+        }
+        self.sheets.push(CharacterSheet {
+            owner: owner.get(),
+            campaign: campaign.to_owned(),
+            name: name.to_owned(),
+            stats: BTreeMap::new(),
+        });
+        self.active.insert(owner.get(), campaign.to_owned());
+        true
+    }
+
+    /// Gets the member's character in the campaign, or their active character if no campaign is
+    /// given
+    pub fn get(&self, owner: serenity::UserId, campaign: Option<&str>) -> Option<&CharacterSheet> {
+        let campaign = campaign.or_else(|| self.active.get(&owner.get()).map(String::as_str))?;
+        self.sheets
+            .iter()
+            .find(|sheet| sheet.is_in(owner, campaign))
+    }
+
+    pub fn get_mut(
+        &mut self,
+        owner: serenity::UserId,
+        campaign: Option<&str>,
+    ) -> Option<&mut CharacterSheet> {
+        let campaign = campaign
+            .or_else(|| self.active.get(&owner.get()).map(String::as_str))?
+            .to_owned();
+        self.sheets
+            .iter_mut()
+            .find(|sheet| sheet.is_in(owner, &campaign))
+    }
+
+    /// Returns false if the member has no character in the campaign
+    pub fn set_active(&mut self, owner: serenity::UserId, campaign: &str) -> bool {
+        let Some(sheet) = self.get(owner, Some(campaign)) else {
+            return false;
+        };
+        let campaign = sheet.campaign.clone();
+        self.active.insert(owner.get(), campaign);
+        true
+    }
+
+    pub fn delete(&mut self, owner: serenity::UserId, campaign: &str) -> Option<CharacterSheet> {
+        let index = self
+            .sheets
+            .iter()
+            .position(|sheet| sheet.is_in(owner, campaign))?;
+        let sheet = self.sheets.remove(index);
+        if self
+            .active
+            .get(&owner.get())
+            .is_some_and(|active| active.eq_ignore_ascii_case(campaign))
+        {
+            self.active.remove(&owner.get());
+        }
+        Some(sheet)
+    }
+
+    /// Every campaign the member has a character in
+    pub fn campaigns(&self, owner: serenity::UserId) -> impl Iterator<Item = &str> {
+        self.sheets
+            .iter()
+            .filter(move |sheet| sheet.owner == owner.get())
+            .map(|sheet| sheet.campaign.as_str())
+    }
+
+    /// Replaces every `$stat` in the expression with that stat of the member's active character.
+    /// Negative stats are wrapped in parentheses.
+    pub fn substitute(
+        &self,
+        owner: serenity::UserId,
+        expression: &str,
+    ) -> Result<String, StatError> {
+        let mut substituted = String::with_capacity(expression.len());
+        let mut rest = expression;
+        while let Some(dollar) = rest.find('$') {
+            let sheet = self.get(owner, None).ok_or(StatError::NoCharacter)?;
+            substituted.push_str(&rest[..dollar]);
+            let after = &rest[dollar + 1..];
+            let len = after
+                .find(|c: char| !is_name_char(c))
+                .unwrap_or(after.len());
+            let stat = after[..len].to_ascii_lowercase();
+            rest = &after[len..];
+
+            let Some(value) = sheet.stat(&stat) else {
+                return Err(StatError::Unknown {
+                    stat,
+                    character: sheet.name.clone(),
+                });
+            };
+            if value < 0 {
+                substituted.push_str(&format!("({value})"));
+            } else {
+                substituted.push_str(&value.to_string());
+            }
+        }
+        substituted.push_str(rest);
+        Ok(substituted)
+    }
+}
+
+/// The seeds that rolls are made with
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct Fairness {
+    /// Keyed by Julian day number, in UTC
+    seeds: BTreeMap<i32, Seed>,
+    next_nonce: u64,
+}
+
+impl Fairness {
+    /// The ID of the next roll, and the RNG to roll it with. The ID is also the roll's nonce.
+    pub fn next_roll(&mut self, at: time::OffsetDateTime) -> (u64, rand_chacha::ChaCha20Rng) {
+        let seed = *self.seed(day_number(at));
+        let nonce = self.next_nonce;
+        self.next_nonce += 1;
+        (nonce, seeded::rng(&seed, nonce))
+    }
+
+    /// The day's seed, which is created the first time it's needed
+    fn seed(&mut self, day: i32) -> &Seed {
+        self.seeds.entry(day).or_insert_with(rand::random)
+    }
+
+    /// The hash of the day's seed, if it has been created
+    pub fn commitment(&self, day: i32) -> Option<Seed> {
+        self.seeds.get(&day).map(seeded::commitment)
+    }
+
+    /// The hash of the day's seed, creating the seed if needed
+    pub fn commit(&mut self, day: i32) -> Seed {
+        seeded::commitment(self.seed(day))
+    }
+
+    /// The day's seed, if the day is over
+    pub fn revealed_seed(&self, day: i32, now: time::OffsetDateTime) -> Option<Seed> {
+        if day >= day_number(now) {
+            return None;
+        }
+        self.seeds.get(&day).copied()
+    }
+}
+
+/// Recent rolls, so that they can be verified. The log is split into chunks of consecutive roll
+/// IDs, each of which is saved separately.
+#[derive(Default)]
+pub struct FairRollLog {
+    /// Keyed by roll ID divided by `CHUNK_LEN`
+    chunks: BTreeMap<u64, Vec<FairRoll>>,
+}
+
+impl FairRollLog {
+    const CHUNK_LEN: u64 = 250;
+    /// Keeps the last 10,000 rolls or so
+    const MAX_CHUNKS: u64 = 40;
+
+    fn key(index: u64) -> String {
+        format!("fair_rolls_{index}")
+    }
+
+    /// Loads the chunks that are recent enough to be kept
+    fn load(persist: &PersistInstance, next_id: u64) -> Self {
+        let newest = next_id / Self::CHUNK_LEN;
+        let oldest = (newest + 1).saturating_sub(Self::MAX_CHUNKS);
+        let chunks = (oldest..=newest)
+            .filter_map(|index| {
+                let chunk = persist.load::<Vec<FairRoll>>(&Self::key(index)).ok()?;
+                Some((index, chunk))
+            })
+            .collect();
+        Self { chunks }
+    }
+
+    /// Returns the index of the chunk the roll was added to, and of any chunks that were dropped
+    /// to make room for it
+    fn push(&mut self, roll: FairRoll) -> (u64, Vec<u64>) {
+        let index = roll.id / Self::CHUNK_LEN;
+        self.chunks.entry(index).or_default().push(roll);
+        let oldest = (index + 1).saturating_sub(Self::MAX_CHUNKS);
+        let kept = self.chunks.split_off(&oldest);
+        let dropped = std::mem::replace(&mut self.chunks, kept);
+        (index, dropped.into_keys().collect())
+    }
+
+    fn chunk(&self, index: u64) -> &[FairRoll] {
+        self.chunks.get(&index).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn get(&self, id: u64) -> Option<&FairRoll> {
+        self.chunk(id / Self::CHUNK_LEN)
+            .iter()
+            .find(|roll| roll.id == id)
+    }
+}
+
+/// The day that seeds are used for
+pub fn day_number(at: time::OffsetDateTime) -> i32 {
+    at.to_offset(time::UtcOffset::UTC).date().to_julian_day()
+}
+
+/// Damage and other tracks for Wildsea ships and characters
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct WildseaTracks {
+    /// Keyed by guild ID
+    guilds: HashMap<u64, Vec<TrackSheet>>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TrackSheet {
+    pub owner: TrackOwner,
+    pub name: String,
+    /// Who made the sheet
+    pub creator: u64,
+    /// Keyed by the track's name in lowercase, like `hull`
+    pub tracks: BTreeMap<String, Track>,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Track {
+    pub marked: u32,
+    pub size: u32,
+}
+
+impl WildseaTracks {
+    pub fn get(
+        &self,
+        guild_id: serenity::GuildId,
+        owner: TrackOwner,
+        name: &str,
+    ) -> Option<&TrackSheet> {
+        self.guilds
+            .get(&guild_id.get())?
+            .iter()
+            .find(|sheet| sheet.owner == owner && sheet.name.eq_ignore_ascii_case(name))
+    }
+
+    fn get_mut(
+        &mut self,
+        guild_id: serenity::GuildId,
+        owner: TrackOwner,
+        name: &str,
+    ) -> Option<&mut TrackSheet> {
+        self.guilds
+            .get_mut(&guild_id.get())?
+            .iter_mut()
+            .find(|sheet| sheet.owner == owner && sheet.name.eq_ignore_ascii_case(name))
+    }
+
+    /// Creates or resizes a track, creating its sheet if needed. A size of 0 removes the track,
+    /// and the sheet along with its last track.
+    pub fn set_track(
+        &mut self,
+        guild_id: serenity::GuildId,
+        creator: serenity::UserId,
+        owner: TrackOwner,
+        name: &str,
+        track: &str,
+        size: u32,
+    ) {
+        let track = track.to_ascii_lowercase();
+        if size == 0 {
+            if let Some(sheet) = self.get_mut(guild_id, owner, name) {
+                sheet.tracks.remove(&track);
+            }
+            if let Some(sheets) = self.guilds.get_mut(&guild_id.get()) {
+                sheets.retain(|sheet| !sheet.tracks.is_empty());
+            }
+            return;
+        }
+
+        if self.get(guild_id, owner, name).is_none() {
+            self.guilds
+                .entry(guild_id.get())
+                .or_default()
+                .push(TrackSheet {
+                    owner,
+                    name: name.to_owned(),
+                    creator: creator.get(),
+                    tracks: BTreeMap::new(),
+                });
+        }
+        let sheet = self.get_mut(guild_id, owner, name).unwrap();
+        let entry = sheet
+            .tracks
+            .entry(track)
+            .or_insert(Track { marked: 0, size });
+        entry.size = size;
+        entry.marked = entry.marked.min(size);
+    }
+
+    /// Marks boxes on the track, or clears them if `amount` is negative. Returns the track
+    /// afterwards, or `None` if it doesn't exist.
+    pub fn mark(
+        &mut self,
+        guild_id: serenity::GuildId,
+        owner: TrackOwner,
+        name: &str,
+        track: &str,
+        amount: i32,
+    ) -> Option<Track> {
+        let sheet = self.get_mut(guild_id, owner, name)?;
+        let track = sheet.tracks.get_mut(&track.to_ascii_lowercase())?;
+        let marked = i64::from(track.marked) + i64::from(amount);
+        track.marked = marked.clamp(0, i64::from(track.size)) as u32;
+        Some(*track)
+    }
+
+    pub fn names(
+        &self,
+        guild_id: serenity::GuildId,
+        owner: TrackOwner,
+    ) -> impl Iterator<Item = &str> {
+        self.guilds
+            .get(&guild_id.get())
+            .into_iter()
+            .flatten()
+            .filter(move |sheet| sheet.owner == owner)
+            .map(|sheet| sheet.name.as_str())
+    }
+}
+
+/// Who sees the secret rolls made in each channel
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct GameMasters {
+    channels: HashMap<u64, GameMaster>,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub enum GameMaster {
+    User(u64),
+    /// Everyone with the role
+    Role(u64),
+}
+
+impl GameMasters {
+    pub fn get(&self, channel_id: serenity::ChannelId) -> Option<GameMaster> {
+        self.channels.get(&channel_id.get()).copied()
+    }
+
+    /// Sets or clears the channel's GM
+    pub fn set(&mut self, channel_id: serenity::ChannelId, game_master: Option<GameMaster>) {
+        match game_master {
+            Some(game_master) => self.channels.insert(channel_id.get(), game_master),
+            None => self.channels.remove(&channel_id.get()),
+        };
+    }
+}
+
+impl GameMaster {
+    pub fn mention(self) -> serenity::Mention {
+        match self {
+            Self::User(id) => serenity::UserId::new(id).into(),
+            Self::Role(id) => serenity::RoleId::new(id).into(),
+        }
+    }
+}
+
 #[derive(Default, Clone, Serialize, Deserialize)]
 pub struct ReplyCommands {
     commands: Vec<ReplyCommand>,
@@ -427,3 +1380,160 @@ impl ReplyCommands {
         self.commands.iter_mut()
     }
 }
+
+#[test]
+fn test_expand_dice_macros() {
+    let user = serenity::UserId::new(1);
+    let guild = Some(serenity::GuildId::new(2));
+    let mut macros = DiceMacros::default();
+    let mut save = |owner, name: &str, expression: &str| {
+        macros.save(owner, name.to_owned(), expression.to_owned(), user, guild)
+    };
+
+    save(MacroOwner::Guild(guild.unwrap()), "str_mod", "1").unwrap();
+    save(MacroOwner::User(user), "str_mod", "3").unwrap();
+    save(MacroOwner::User(user), "prof", "2").unwrap();
+    save(MacroOwner::User(user), "attack", "1d20+@str_mod+@prof").unwrap();
+    assert_eq!(
+        save(MacroOwner::User(user), "prof", "@attack"),
+        Err(MacroError::Cycle(String::from("prof")))
+    );
+    assert_eq!(
+        save(MacroOwner::User(user), "damage", "1d8+@dex_mod"),
+        Err(MacroError::Unknown(String::from("dex_mod")))
+    );
+    save(MacroOwner::User(user), "a", "1d6+1d6").unwrap();
+    for (name, previous) in [("b", "a"), ("c", "b"), ("d", "c"), ("e", "d"), ("f", "e")] {
+        let expression = format!("@{previous}+@{previous}");
+        save(MacroOwner::User(user), name, &expression).unwrap();
+    }
+    assert_eq!(
+        save(MacroOwner::User(user), "g", "@f+@f+@f+@f+@f+@f+@f+@f"),
+        Err(MacroError::TooLong)
+    );
+
+    assert_eq!(macros.creator(guild.unwrap(), "str_mod"), Some(user));
+    assert_eq!(macros.expand("@attack", user, guild).unwrap(), "(1d20+3+2)");
+    assert_eq!(
+        macros.expand("@ATTACK + 1", user, None).unwrap(),
+        "(1d20+3+2) + 1"
+    );
+    assert_eq!(
+        macros
+            .expand("1d20+@str_mod", serenity::UserId::new(3), guild)
+            .unwrap(),
+        "1d20+1"
+    );
+}
+
+#[test]
+fn test_substitute_character_stats() {
+    let owner = serenity::UserId::new(1);
+    let mut characters = Characters::default();
+    assert_eq!(
+        characters.substitute(owner, "1d20+$dex"),
+        Err(StatError::NoCharacter)
+    );
+    assert_eq!(characters.substitute(owner, "1d20+2").unwrap(), "1d20+2");
+
+    characters.create(owner, "Strahd", "Ireena");
+    let sheet = characters.get_mut(owner, None).unwrap();
+    sheet.stats.insert(String::from("dex"), 8);
+    sheet.stats.insert(String::from("prof"), 2);
+    sheet.stats.insert(String::from("str"), 17);
+    assert_eq!(
+        characters.substitute(owner, "1d20+$DEX_mod+$prof").unwrap(),
+        "1d20+(-1)+2"
+    );
+    assert_eq!(
+        characters.substitute(owner, "1d20+$str_mod").unwrap(),
+        "1d20+3"
+    );
+    assert!(matches!(
+        characters.substitute(owner, "1d20+$wis"),
+        Err(StatError::Unknown { .. })
+    ));
+}
+
+#[test]
+fn test_wildsea_tracks() {
+    let guild = serenity::GuildId::new(1);
+    let user = serenity::UserId::new(2);
+    let mut tracks = WildseaTracks::default();
+    tracks.set_track(guild, user, TrackOwner::Ship, "Gull", "Hull", 4);
+    tracks.set_track(guild, user, TrackOwner::Character, "Gull", "mire", 2);
+
+    let hull = |tracks: &WildseaTracks| {
+        tracks.get(guild, TrackOwner::Ship, "gull").unwrap().tracks["hull"]
+    };
+    assert_eq!(
+        tracks.mark(guild, TrackOwner::Ship, "GULL", "hull", 3),
+        Some(Track { marked: 3, size: 4 })
+    );
+    assert_eq!(
+        tracks
+            .mark(guild, TrackOwner::Ship, "Gull", "hull", 5)
+            .unwrap()
+            .marked,
+        4
+    );
+    assert_eq!(
+        tracks.mark(guild, TrackOwner::Ship, "Gull", "crew", 1),
+        None
+    );
+
+    tracks.set_track(guild, user, TrackOwner::Ship, "Gull", "hull", 2);
+    assert_eq!(hull(&tracks), Track { marked: 2, size: 2 });
+    assert_eq!(
+        tracks
+            .mark(guild, TrackOwner::Ship, "Gull", "hull", -3)
+            .unwrap()
+            .marked,
+        0
+    );
+
+    tracks.set_track(guild, user, TrackOwner::Ship, "Gull", "hull", 0);
+    assert!(tracks.get(guild, TrackOwner::Ship, "Gull").is_none());
+    assert_eq!(
+        tracks
+            .names(guild, TrackOwner::Character)
+            .collect::<Vec<_>>(),
+        ["Gull"]
+    );
+    assert_eq!(
+        tracks
+            .get(guild, TrackOwner::Character, "gull")
+            .unwrap()
+            .creator,
+        user.get()
+    );
+    assert!(tracks
+        .get(serenity::GuildId::new(3), TrackOwner::Character, "Gull")
+        .is_none());
+}
+
+#[test]
+fn test_fair_roll_log() {
+    let roll = |id| FairRoll {
+        id,
+        kind: crate::commands::FairRollKind::Dice {
+            expression: String::from("1d20"),
+        },
+        dice: vec![20],
+        at: OffsetDateTime::UNIX_EPOCH,
+        user: 1,
+        private: false,
+        secret_for: None,
+    };
+    let mut log = FairRollLog::default();
+    assert_eq!(log.push(roll(0)), (0, vec![]));
+    assert_eq!(log.push(roll(FairRollLog::CHUNK_LEN + 1)), (1, vec![]));
+    assert_eq!(log.chunk(1).len(), 1);
+    assert_eq!(log.get(FairRollLog::CHUNK_LEN + 1).unwrap().dice, [20]);
+    assert!(log.get(1).is_none());
+
+    let newest = FairRollLog::MAX_CHUNKS * FairRollLog::CHUNK_LEN;
+    assert_eq!(log.push(roll(newest)), (FairRollLog::MAX_CHUNKS, vec![0]));
+    assert!(log.get(0).is_none());
+    assert!(log.get(FairRollLog::CHUNK_LEN + 1).is_some());
+}
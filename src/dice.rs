@@ -0,0 +1,526 @@
+//! Patbot's dice engine. It understands the most common dice notation (`NdM`, keep/drop modifiers
+//! and constants), which is enough to roll with a chosen RNG and to compute exact outcome
+//! distributions.
+
+mod distribution;
+pub mod render;
+pub mod seeded;
+
+pub use distribution::{Budget, Distribution};
+
+use rand::Rng;
+use std::fmt;
+
+const MAX_DICE: u32 = 100;
+const MAX_SIDES: u32 = 1000;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Expr {
+    terms: Vec<Term>,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct Term {
+    negative: bool,
+    kind: TermKind,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum TermKind {
+    Constant(u32),
+    Dice(Dice),
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Dice {
+    pub count: u32,
+    pub sides: u32,
+    pub keep: Keep,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Keep {
+    All,
+    Highest(u32),
+    Lowest(u32),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ParseError(String);
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl std::str::FromStr for Expr {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let chars: Vec<char> = s
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+        let mut parser = Parser { chars, pos: 0 };
+        let terms = parser.sum()?;
+        match parser.peek() {
+            Some(c) => Err(ParseError(format!("unexpected `{c}`"))),
+            None => Ok(Self { terms }),
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let matches = self.peek() == Some(c);
+        if matches {
+            self.pos += 1;
+        }
+        matches
+    }
+
+    fn number(&mut self) -> Result<Option<u32>, ParseError> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Ok(None);
+        }
+        let digits: String = self.chars[start..self.pos].iter().collect();
+        digits
+            .parse()
+            .map(Some)
+            .map_err(|_| ParseError(format!("`{digits}` is too large")))
+    }
+
+    /// Terms separated by `+` and `-`. Sums in parentheses are flattened into the outer sum.
+    fn sum(&mut self) -> Result<Vec<Term>, ParseError> {
+        let mut terms = Vec::new();
+        let mut negative = self.eat('-');
+        if !negative {
+            self.eat('+');
+        }
+        loop {
+            if self.eat('(') {
+                let inner = self.sum()?;
+                if !self.eat(')') {
+                    return Err(ParseError(String::from("expected `)`")));
+                }
+                terms.extend(inner.into_iter().map(|term| Term {
+                    negative: term.negative != negative,
+                    ..term
+                }));
+            } else {
+                terms.push(Term {
+                    negative,
+                    kind: self.term()?,
+                });
+            }
+
+            if self.eat('+') {
+                negative = false;
+            } else if self.eat('-') {
+                negative = true;
+            } else {
+                return Ok(terms);
+            }
+        }
+    }
+
+    fn term(&mut self) -> Result<TermKind, ParseError> {
+        let count = self.number()?;
+        if !self.eat('d') {
+            return match count {
+                Some(n) => Ok(TermKind::Constant(n)),
+                None => Err(match self.peek() {
+                    Some(c) => ParseError(format!("unexpected `{c}`")),
+                    None => ParseError(String::from("unexpected end of expression")),
+                }),
+            };
+        }
+
+        let count = count.unwrap_or(1);
+        let Some(sides) = self.number()? else {
+            return Err(ParseError(String::from(
+                "expected a number of sides after `d`",
+            )));
+        };
+        if !(1..=MAX_DICE).contains(&count) {
+            return Err(ParseError(format!(
+                "you can roll 1 to {MAX_DICE} dice at once"
+            )));
+        }
+        if !(1..=MAX_SIDES).contains(&sides) {
+            return Err(ParseError(format!("dice can have 1 to {MAX_SIDES} sides")));
+        }
+
+        let keep = if self.eat('k') {
+            let lowest = self.eat('l');
+            if !lowest {
+                self.eat('h');
+            }
+            let n = self.number()?.unwrap_or(1);
+            if n > count {
+                return Err(ParseError(format!("cannot keep {n} of {count} dice")));
+            }
+            if lowest {
+                Keep::Lowest(n)
+            } else {
+                Keep::Highest(n)
+            }
+        } else if self.eat('d') {
+            let highest = self.eat('h');
+            if !highest {
+                self.eat('l');
+            }
+            let n = self.number()?.unwrap_or(1);
+            if n > count {
+                return Err(ParseError(format!("cannot drop {n} of {count} dice")));
+            }
+            if highest {
+                Keep::Lowest(count - n)
+            } else {
+                Keep::Highest(count - n)
+            }
+        } else {
+            Keep::All
+        };
+
+        Ok(TermKind::Dice(Dice { count, sides, keep }))
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, term) in self.terms.iter().enumerate() {
+            if term.negative {
+                f.write_str("-")?;
+            } else if i > 0 {
+                f.write_str("+")?;
+            }
+            match term.kind {
+                TermKind::Constant(n) => write!(f, "{n}")?,
+                TermKind::Dice(dice) => write!(f, "{dice}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Dice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}d{}", self.count, self.sides)?;
+        match self.keep {
+            Keep::All => Ok(()),
+            Keep::Highest(n) => write!(f, "kh{n}"),
+            Keep::Lowest(n) => write!(f, "kl{n}"),
+        }
+    }
+}
+
+impl Dice {
+    fn kept_count(self) -> u32 {
+        match self.keep {
+            Keep::All => self.count,
+            Keep::Highest(n) | Keep::Lowest(n) => n,
+        }
+    }
+}
+
+impl Expr {
+    fn dice(&self) -> impl Iterator<Item = (bool, Dice)> + '_ {
+        self.terms.iter().filter_map(|term| match term.kind {
+            TermKind::Dice(dice) => Some((term.negative, dice)),
+            TermKind::Constant(_) => None,
+        })
+    }
+
+    /// The sum of the expression's constant terms
+    pub fn modifier(&self) -> i64 {
+        self.terms
+            .iter()
+            .map(|term| match term.kind {
+                TermKind::Constant(n) if term.negative => -i64::from(n),
+                TermKind::Constant(n) => i64::from(n),
+                TermKind::Dice(_) => 0,
+            })
+            .sum()
+    }
+
+    /// If the expression keeps exactly one d20 and everything else is a constant, this returns the
+    /// number that the d20 landed on.
+    pub fn natural_d20(&self, total: i64) -> Option<u32> {
+        let mut dice = self.dice();
+        let (negative, d20) = dice.next()?;
+        if negative || dice.next().is_some() || d20.sides != 20 || d20.kept_count() != 1 {
+            return None;
+        }
+        u32::try_from(total - self.modifier())
+            .ok()
+            .filter(|natural| (1..=20).contains(natural))
+    }
+
+    /// Rolls each lone d20 twice, keeping the highest (advantage) or the lowest (disadvantage). A
+    /// d20 already rolled with advantage or disadvantage is switched. Returns `None` if there is
+    /// no d20 to change.
+    pub fn with_advantage(&self, highest: bool) -> Option<Self> {
+        let mut found = false;
+        let terms = self
+            .terms
+            .iter()
+            .map(|&term| match term.kind {
+                TermKind::Dice(dice)
+                    if dice.sides == 20 && dice.count <= 2 && dice.kept_count() == 1 =>
+                {
+                    found = true;
+                    let keep = if highest {
+                        Keep::Highest(1)
+                    } else {
+                        Keep::Lowest(1)
+                    };
+                    Term {
+                        kind: TermKind::Dice(Dice {
+                            count: 2,
+                            sides: 20,
+                            keep,
+                        }),
+                        ..term
+                    }
+                }
+                _ => term,
+            })
+            .collect();
+        found.then_some(Self { terms })
+    }
+
+    /// Adds `n` to the expression, combining its constants into one at the end
+    pub fn with_modifier(&self, n: i64) -> Self {
+        let mut terms: Vec<_> = self
+            .terms
+            .iter()
+            .copied()
+            .filter(|term| matches!(term.kind, TermKind::Dice(_)))
+            .collect();
+        let modifier = self.modifier() + n;
+        if modifier != 0 || terms.is_empty() {
+            terms.push(Term {
+                negative: modifier < 0,
+                kind: TermKind::Constant(
+                    u32::try_from(modifier.unsigned_abs()).unwrap_or(u32::MAX),
+                ),
+            });
+        }
+        Self { terms }
+    }
+
+    /// Doubles the number of dice rolled, as on a critical hit. Returns `None` if that would be
+    /// too many dice.
+    pub fn with_double_dice(&self) -> Option<Self> {
+        let terms = self
+            .terms
+            .iter()
+            .map(|&term| {
+                let TermKind::Dice(dice) = term.kind else {
+                    return Some(term);
+                };
+                let count = dice.count * 2;
+                if count > MAX_DICE {
+                    return None;
+                }
+                let keep = match dice.keep {
+                    Keep::All => Keep::All,
+                    Keep::Highest(n) => Keep::Highest(n * 2),
+                    Keep::Lowest(n) => Keep::Lowest(n * 2),
+                };
+                Some(Term {
+                    kind: TermKind::Dice(Dice {
+                        count,
+                        sides: dice.sides,
+                        keep,
+                    }),
+                    ..term
+                })
+            })
+            .collect::<Option<_>>()?;
+        Some(Self { terms })
+    }
+
+    pub fn roll(&self, rng: &mut impl Rng) -> Roll {
+        let terms: Vec<_> = self
+            .terms
+            .iter()
+            .map(|term| RolledTerm {
+                negative: term.negative,
+                kind: match term.kind {
+                    TermKind::Constant(n) => RolledKind::Constant(n),
+                    TermKind::Dice(dice) => RolledKind::Dice {
+                        dice,
+                        rolls: roll_dice(dice, rng),
+                    },
+                },
+            })
+            .collect();
+        let total = terms.iter().map(RolledTerm::value).sum();
+        Roll { terms, total }
+    }
+
+    /// The exact distribution of the expression's total. Returns `None` if it would take too long
+    /// to compute.
+    pub fn distribution(&self) -> Option<Distribution> {
+        self.distribution_within(&mut Budget::default())
+    }
+
+    /// Like [`Expr::distribution`], but takes the work out of `budget` so that it can be shared
+    /// between expressions
+    pub fn distribution_within(&self, budget: &mut Budget) -> Option<Distribution> {
+        self.terms
+            .iter()
+            .try_fold(Distribution::constant(0), |acc, term| {
+                let distribution = match term.kind {
+                    TermKind::Constant(n) => Distribution::constant(n.into()),
+                    TermKind::Dice(dice) => Distribution::dice(dice, budget)?,
+                };
+                let distribution = if term.negative {
+                    distribution.negate()
+                } else {
+                    distribution
+                };
+                acc.add_within(&distribution, budget)
+            })
+    }
+}
+
+fn roll_dice(dice: Dice, rng: &mut impl Rng) -> Vec<DieRoll> {
+    let mut rolls: Vec<_> = (0..dice.count)
+        .map(|_| DieRoll {
+            value: seeded::die(rng, dice.sides),
+            kept: true,
+        })
+        .collect();
+
+    let (n, highest) = match dice.keep {
+        Keep::All => return rolls,
+        Keep::Highest(n) => (n, true),
+        Keep::Lowest(n) => (n, false),
+    };
+    let mut order: Vec<usize> = (0..rolls.len()).collect();
+    order.sort_by_key(|&i| rolls[i].value);
+    if highest {
+        order.reverse();
+    }
+    for &i in &order[n as usize..] {
+        rolls[i].kept = false;
+    }
+    rolls
+}
+
+#[derive(Debug, Clone)]
+pub struct Roll {
+    pub terms: Vec<RolledTerm>,
+    pub total: i64,
+}
+
+#[derive(Debug, Clone)]
+pub struct RolledTerm {
+    pub negative: bool,
+    pub kind: RolledKind,
+}
+
+#[derive(Debug, Clone)]
+pub enum RolledKind {
+    Constant(u32),
+    Dice { dice: Dice, rolls: Vec<DieRoll> },
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct DieRoll {
+    pub value: u32,
+    /// Whether the die counts towards the total, or was dropped by a keep/drop modifier
+    pub kept: bool,
+}
+
+impl RolledTerm {
+    fn value(&self) -> i64 {
+        let value = match &self.kind {
+            RolledKind::Constant(n) => i64::from(*n),
+            RolledKind::Dice { rolls, .. } => rolls
+                .iter()
+                .filter(|roll| roll.kept)
+                .map(|roll| i64::from(roll.value))
+                .sum(),
+        };
+        if self.negative {
+            -value
+        } else {
+            value
+        }
+    }
+}
+
+#[test]
+fn test_parse_expr() {
+    let parse = |s: &str| s.parse::<Expr>().map(|expr| expr.to_string());
+    assert_eq!(parse("d20").unwrap(), "1d20");
+    assert_eq!(parse("2d20kh1 + 5").unwrap(), "2d20kh1+5");
+    assert_eq!(parse("4d6dl").unwrap(), "4d6kh3");
+    assert_eq!(parse("-1+3D8K2").unwrap(), "-1+3d8kh2");
+    assert_eq!(parse("2d20dh1-2").unwrap(), "2d20kl1-2");
+    assert_eq!(parse("1d20-(1d4+2)").unwrap(), "1d20-1d4-2");
+    assert!(parse("2d").is_err());
+    assert!(parse("1d20+").is_err());
+    assert!(parse("3d6kh4").is_err());
+    assert!(parse("1d20 * 2").is_err());
+    assert!(parse("(1d20").is_err());
+}
+
+#[test]
+fn test_natural_d20() {
+    let expr: Expr = "2d20kh1+5".parse().unwrap();
+    assert_eq!(expr.natural_d20(25), Some(20));
+    assert_eq!(expr.natural_d20(6), Some(1));
+    assert_eq!("1d20+1d4".parse::<Expr>().unwrap().natural_d20(10), None);
+    assert_eq!("2d20".parse::<Expr>().unwrap().natural_d20(10), None);
+}
+
+#[test]
+fn test_modify_expr() {
+    let expr: Expr = "1d20+5-1".parse().unwrap();
+    let advantage = expr.with_advantage(true).unwrap();
+    assert_eq!(advantage.to_string(), "2d20kh1+5-1");
+    assert_eq!(
+        advantage.with_advantage(false).unwrap().to_string(),
+        "2d20kl1+5-1"
+    );
+    assert_eq!(expr.with_modifier(1).to_string(), "1d20+5");
+    assert_eq!(expr.with_modifier(-6).to_string(), "1d20-2");
+    assert_eq!(expr.with_modifier(-4).to_string(), "1d20");
+    assert_eq!(
+        "2d6+1d8kh1+3"
+            .parse::<Expr>()
+            .unwrap()
+            .with_double_dice()
+            .unwrap()
+            .to_string(),
+        "4d6+2d8kh2+3"
+    );
+    assert!("2d6"
+        .parse::<Expr>()
+        .unwrap()
+        .with_advantage(true)
+        .is_none());
+    assert!("60d6".parse::<Expr>().unwrap().with_double_dice().is_none());
+}
@@ -0,0 +1,265 @@
+use super::{Dice, Keep};
+
+/// Roughly how many steps computing a distribution may take before we give up on it
+const MAX_WORK: u64 = 50_000_000;
+
+/// How many more steps computing distributions may take
+#[derive(Debug)]
+pub struct Budget {
+    work: u64,
+}
+
+impl Budget {
+    pub fn new(work: u64) -> Self {
+        Self { work }
+    }
+
+    /// Returns `None` if there isn't enough work left
+    fn spend(&mut self, work: u64) -> Option<()> {
+        self.work = self.work.checked_sub(work)?;
+        Some(())
+    }
+}
+
+impl Default for Budget {
+    fn default() -> Self {
+        Self::new(MAX_WORK)
+    }
+}
+
+/// The probability of every possible total of a dice expression
+#[derive(Debug, Clone)]
+pub struct Distribution {
+    min: i64,
+    /// The probability of rolling each total, starting from `min`
+    probabilities: Vec<f64>,
+}
+
+impl Distribution {
+    pub(super) fn constant(n: i64) -> Self {
+        Self {
+            min: n,
+            probabilities: vec![1.0],
+        }
+    }
+
+    /// Returns `None` if the distribution would take more than the rest of `budget` to compute
+    pub(super) fn dice(dice: Dice, budget: &mut Budget) -> Option<Self> {
+        let count = u64::from(dice.count);
+        let sides = u64::from(dice.sides);
+        match dice.keep {
+            Keep::All => {
+                budget.spend(count * count * sides * sides)?;
+                Some(Self::sum_of_dice(dice.count, dice.sides))
+            }
+            Keep::Highest(keep) | Keep::Lowest(keep) => {
+                budget.spend(count * count * u64::from(keep) * sides * sides)?;
+                let highest = Self::keep_highest(dice.count, dice.sides, keep);
+                Some(match dice.keep {
+                    // Keeping the lowest dice is the same as keeping the highest dice with their
+                    // faces numbered backwards
+                    Keep::Lowest(_) => {
+                        let offset = i64::from(keep) * (i64::from(dice.sides) + 1);
+                        highest.negate().shift(offset)
+                    }
+                    _ => highest,
+                })
+            }
+        }
+    }
+
+    fn sum_of_dice(count: u32, sides: u32) -> Self {
+        let die = Self {
+            min: 1,
+            probabilities: vec![1.0 / f64::from(sides); sides as usize],
+        };
+        (0..count).fold(Self::constant(0), |acc, _| acc.add(&die))
+    }
+
+    fn keep_highest(count: u32, sides: u32, keep: u32) -> Self {
+        let (count, sides, keep) = (count as usize, sides as usize, keep as usize);
+        let binomials = binomials(count);
+        let face_probability = 1.0 / sides as f64;
+        let max_sum = keep * sides;
+
+        // Going from the highest face to the lowest, decide how many dice landed on each face.
+        // `weights[assigned][sum]` is the probability that `assigned` dice landed on the faces
+        // seen so far, and the kept dice among them add up to `sum`.
+        let mut weights = vec![vec![0.0; max_sum + 1]; count + 1];
+        weights[0][0] = 1.0;
+        for face in (1..=sides).rev() {
+            let mut next = vec![vec![0.0; max_sum + 1]; count + 1];
+            for assigned in 0..=count {
+                for sum in 0..=max_sum {
+                    let weight = weights[assigned][sum];
+                    if weight == 0.0 {
+                        continue;
+                    }
+                    let mut probability = 1.0;
+                    for extra in 0..=count - assigned {
+                        let kept = (assigned + extra).min(keep) - assigned.min(keep);
+                        next[assigned + extra][sum + kept * face] +=
+                            weight * binomials[count - assigned][extra] * probability;
+                        probability *= face_probability;
+                    }
+                }
+            }
+            weights = next;
+        }
+
+        Self {
+            min: keep as i64,
+            probabilities: weights.swap_remove(count).split_off(keep),
+        }
+    }
+
+    /// Like [`Distribution::add`], but returns `None` if it would take more than the rest of
+    /// `budget`
+    pub(super) fn add_within(&self, other: &Self, budget: &mut Budget) -> Option<Self> {
+        let work = self.probabilities.len() as u64 * other.probabilities.len() as u64;
+        budget.spend(work)?;
+        Some(self.add(other))
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        let mut probabilities = vec![0.0; self.probabilities.len() + other.probabilities.len() - 1];
+        for (i, &a) in self.probabilities.iter().enumerate() {
+            for (j, &b) in other.probabilities.iter().enumerate() {
+                probabilities[i + j] += a * b;
+            }
+        }
+        Self {
+            min: self.min + other.min,
+            probabilities,
+        }
+    }
+
+    pub(super) fn negate(mut self) -> Self {
+        self.min = -self.max();
+        self.probabilities.reverse();
+        self
+    }
+
+    fn shift(mut self, offset: i64) -> Self {
+        self.min += offset;
+        self
+    }
+
+    pub fn min(&self) -> i64 {
+        self.min
+    }
+
+    pub fn max(&self) -> i64 {
+        self.min + self.probabilities.len() as i64 - 1
+    }
+
+    /// Every possible total and its probability, from lowest to highest
+    pub fn iter(&self) -> impl Iterator<Item = (i64, f64)> + '_ {
+        (self.min..).zip(self.probabilities.iter().copied())
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.iter().map(|(total, p)| total as f64 * p).sum()
+    }
+
+    pub fn std_dev(&self) -> f64 {
+        let mean = self.mean();
+        let variance: f64 = self
+            .iter()
+            .map(|(total, p)| (total as f64 - mean).powi(2) * p)
+            .sum();
+        variance.sqrt()
+    }
+
+    /// The chance of rolling `target` or higher
+    pub fn at_least(&self, target: i64) -> f64 {
+        self.iter()
+            .filter(|&(total, _)| total >= target)
+            .map(|(_, p)| p)
+            .sum()
+    }
+
+    /// How lucky a total is, from 0 (the worst possible result) to 1 (the best). Ties are split
+    /// evenly, so an average roller scores 0.5 over time.
+    pub fn percentile(&self, total: i64) -> f64 {
+        let below: f64 = self
+            .iter()
+            .take_while(|&(n, _)| n < total)
+            .map(|(_, p)| p)
+            .sum();
+        let equal = self
+            .iter()
+            .find(|&(n, _)| n == total)
+            .map_or(0.0, |(_, p)| p);
+        below + equal / 2.0
+    }
+}
+
+/// Pascal's triangle up to row `n`
+fn binomials(n: usize) -> Vec<Vec<f64>> {
+    let mut rows: Vec<Vec<f64>> = Vec::with_capacity(n + 1);
+    for i in 0..=n {
+        let row = (0..=i)
+            .map(|j| match j {
+                0 => 1.0,
+                j if j == i => 1.0,
+                j => rows[i - 1][j - 1] + rows[i - 1][j],
+            })
+            .collect();
+        rows.push(row);
+    }
+    rows
+}
+
+#[test]
+fn test_distribution() {
+    let distribution = |s: &str| s.parse::<super::Expr>().unwrap().distribution().unwrap();
+    let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+
+    let d = distribution("2d6");
+    assert_eq!((d.min(), d.max()), (2, 12));
+    assert!(close(d.mean(), 7.0));
+    assert!(close(
+        d.iter().find(|&(n, _)| n == 7).unwrap().1,
+        6.0 / 36.0
+    ));
+
+    let d = distribution("2d20kh1+5");
+    assert_eq!((d.min(), d.max()), (6, 25));
+    assert!(close(
+        d.iter().find(|&(n, _)| n == 25).unwrap().1,
+        39.0 / 400.0
+    ));
+    assert!(close(d.mean(), 13.825 + 5.0));
+    assert!(close(d.at_least(15), 1.0 - 0.45 * 0.45));
+
+    let d = distribution("2d20kl1");
+    assert!(close(
+        d.iter().find(|&(n, _)| n == 1).unwrap().1,
+        39.0 / 400.0
+    ));
+    assert!(close(d.mean(), 21.0 - 13.825));
+
+    let d = distribution("4d6kh3");
+    assert_eq!((d.min(), d.max()), (3, 18));
+    assert!(close(d.mean(), 15869.0 / 1296.0));
+
+    let d = distribution("1d4-1d4");
+    assert_eq!((d.min(), d.max()), (-3, 3));
+    assert!(close(d.mean(), 0.0));
+    assert!(close(d.percentile(0), 0.5));
+
+    assert!(close(d.iter().map(|(_, p)| p).sum(), 1.0));
+    assert!("100d1000"
+        .parse::<super::Expr>()
+        .unwrap()
+        .distribution()
+        .is_none());
+    // Every term is cheap on its own, but adding them all up isn't
+    assert!(vec!["1d1000"; 60]
+        .join("+")
+        .parse::<super::Expr>()
+        .unwrap()
+        .distribution()
+        .is_none());
+}
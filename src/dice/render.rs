@@ -0,0 +1,207 @@
+//! Draws the dice of a roll as a PNG, for rolls with too many dice to show as text
+
+use super::{Roll, RolledKind};
+
+/// The width and height of each die, in pixels
+const TILE: u32 = 56;
+const GAP: u32 = 8;
+const PER_ROW: u32 = 10;
+/// Expressions can have any number of terms, so this keeps the image from getting too big
+const MAX_FACES: usize = 100;
+/// How many pixels each pixel of the font is drawn as
+const SCALE: u32 = 3;
+const CORNER: u32 = 6;
+const BORDER: u32 = 3;
+
+type Rgba = [u8; 4];
+
+const TILE_COLOR: Rgba = [0xf2, 0xf3, 0xf5, 0xff];
+const TEXT_COLOR: Rgba = [0x2b, 0x2d, 0x31, 0xff];
+const DROPPED_COLOR: Rgba = [0x80, 0x84, 0x8e, 0xff];
+const DROPPED_TEXT_COLOR: Rgba = [0xc4, 0xc9, 0xce, 0xff];
+const CRIT_COLOR: Rgba = [0xf0, 0xb2, 0x32, 0xff];
+const FUMBLE_COLOR: Rgba = [0xf2, 0x3f, 0x43, 0xff];
+
+/// A 3x5 font for the digits, one row per byte
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b001, 0b001, 0b001],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Face {
+    Normal,
+    /// Dropped by a keep/drop modifier
+    Dropped,
+    /// The highest face of the die
+    Crit,
+    /// A 1
+    Fumble,
+}
+
+struct Canvas {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; (width * height * 4) as usize],
+        }
+    }
+
+    fn fill(&mut self, x: u32, y: u32, width: u32, height: u32, color: Rgba) {
+        for py in y..y + height {
+            for px in x..x + width {
+                let i = ((py * self.width + px) * 4) as usize;
+                self.pixels[i..i + 4].copy_from_slice(&color);
+            }
+        }
+    }
+
+    /// Fills a square with its corners cut off
+    fn tile(&mut self, x: u32, y: u32, size: u32, color: Rgba) {
+        self.fill(x + CORNER, y, size - 2 * CORNER, size, color);
+        self.fill(x, y + CORNER, size, size - 2 * CORNER, color);
+        for i in 0..CORNER {
+            // A diagonal from the edge to the corner's inset
+            let inset = CORNER - i;
+            self.fill(x + inset, y + i, size - 2 * inset, 1, color);
+            self.fill(x + inset, y + size - 1 - i, size - 2 * inset, 1, color);
+        }
+    }
+
+    fn text(&mut self, x: u32, y: u32, text: &str, color: Rgba) {
+        for (i, digit) in text.bytes().enumerate() {
+            let glyph = DIGITS[(digit - b'0') as usize];
+            let gx = x + i as u32 * 4 * SCALE;
+            for (row, bits) in glyph.iter().enumerate() {
+                for col in 0..3 {
+                    if bits & (0b100 >> col) != 0 {
+                        let px = gx + col * SCALE;
+                        let py = y + row as u32 * SCALE;
+                        self.fill(px, py, SCALE, SCALE, color);
+                    }
+                }
+            }
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().unwrap();
+        writer.write_image_data(&self.pixels).unwrap();
+        writer.finish().unwrap();
+        bytes
+    }
+}
+
+/// The roll's dice, in order, and how each should be drawn
+fn faces(roll: &Roll) -> Vec<(u32, Face)> {
+    roll.terms
+        .iter()
+        .filter_map(|term| match &term.kind {
+            RolledKind::Dice { dice, rolls } => Some((dice.sides, rolls)),
+            RolledKind::Constant(_) => None,
+        })
+        .flat_map(|(sides, rolls)| {
+            rolls.iter().map(move |roll| {
+                let face = if !roll.kept {
+                    Face::Dropped
+                } else if roll.value == sides && sides > 1 {
+                    Face::Crit
+                } else if roll.value == 1 && sides > 1 {
+                    Face::Fumble
+                } else {
+                    Face::Normal
+                };
+                (roll.value, face)
+            })
+        })
+        .collect()
+}
+
+/// Draws each die of the roll as a tile with its value. Returns `None` if the roll has no dice, or
+/// more than `MAX_FACES`.
+pub fn render(roll: &Roll) -> Option<Vec<u8>> {
+    let faces = faces(roll);
+    if faces.is_empty() || faces.len() > MAX_FACES {
+        return None;
+    }
+
+    let count = faces.len() as u32;
+    let columns = count.min(PER_ROW);
+    let rows = count.div_ceil(PER_ROW);
+    let mut canvas = Canvas::new(columns * (TILE + GAP) + GAP, rows * (TILE + GAP) + GAP);
+
+    for (i, &(value, face)) in faces.iter().enumerate() {
+        let i = i as u32;
+        let x = GAP + (i % PER_ROW) * (TILE + GAP);
+        let y = GAP + (i / PER_ROW) * (TILE + GAP);
+        let (tile, text) = match face {
+            Face::Normal => (TILE_COLOR, TEXT_COLOR),
+            Face::Dropped => (DROPPED_COLOR, DROPPED_TEXT_COLOR),
+            Face::Crit => (CRIT_COLOR, TEXT_COLOR),
+            Face::Fumble => (FUMBLE_COLOR, TILE_COLOR),
+        };
+        // Crits and fumbles get a light border, so they stand out from the other dice
+        if matches!(face, Face::Crit | Face::Fumble) {
+            canvas.tile(x, y, TILE, TILE_COLOR);
+            canvas.tile(x + BORDER, y + BORDER, TILE - 2 * BORDER, tile);
+        } else {
+            canvas.tile(x, y, TILE, tile);
+        }
+
+        let value = value.to_string();
+        let text_width = value.len() as u32 * 4 * SCALE - SCALE;
+        let text_x = x + (TILE - text_width) / 2;
+        let text_y = y + (TILE - 5 * SCALE) / 2;
+        canvas.text(text_x, text_y, &value, text);
+    }
+
+    Some(canvas.encode())
+}
+
+#[test]
+fn test_render() {
+    use rand::SeedableRng;
+
+    let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+    let roll = "25d6kh20+3".parse::<super::Expr>().unwrap().roll(&mut rng);
+    let faces = faces(&roll);
+    assert_eq!(faces.len(), 25);
+    assert_eq!(
+        faces
+            .iter()
+            .filter(|(_, face)| *face == Face::Dropped)
+            .count(),
+        5
+    );
+
+    let png = render(&roll).unwrap();
+    assert_eq!(&png[1..4], b"PNG");
+    // 10 dice per row, 3 rows
+    let width = u32::from_be_bytes(png[16..20].try_into().unwrap());
+    let height = u32::from_be_bytes(png[20..24].try_into().unwrap());
+    assert_eq!((width, height), (648, 200));
+
+    let constant = "5".parse::<super::Expr>().unwrap().roll(&mut rng);
+    assert!(render(&constant).is_none());
+    let many = "100d6+1d6".parse::<super::Expr>().unwrap().roll(&mut rng);
+    assert!(render(&many).is_none());
+}
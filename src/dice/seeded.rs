@@ -0,0 +1,58 @@
+//! Rolls that anyone can check afterwards. Each day has a random seed whose hash is published
+//! while it's in use, and which is revealed once the day is over. Every roll's RNG is seeded with
+//! the day's seed and the roll's nonce, so it can be recomputed from them.
+
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use sha2::{Digest, Sha256};
+
+pub type Seed = [u8; 32];
+
+/// The RNG for a roll: ChaCha20 seeded with `SHA-256(seed || nonce)`, where the nonce is 8
+/// little-endian bytes
+pub fn rng(seed: &Seed, nonce: u64) -> ChaCha20Rng {
+    let mut hasher = Sha256::new();
+    hasher.update(seed);
+    hasher.update(nonce.to_le_bytes());
+    ChaCha20Rng::from_seed(hasher.finalize().into())
+}
+
+/// Rolls a die from 1 to `sides`. This doesn't use `Rng::random_range`, whose algorithm could
+/// change between versions of `rand`, so that old rolls can still be recomputed: it takes
+/// `next_u32() % sides + 1`, skipping values from the uneven range at the top of `u32`.
+pub fn die(rng: &mut impl RngCore, sides: u32) -> u32 {
+    let sides = u64::from(sides);
+    let limit = (1 << 32) - (1 << 32) % sides;
+    loop {
+        let value = u64::from(rng.next_u32());
+        if value < limit {
+            return (value % sides) as u32 + 1;
+        }
+    }
+}
+
+/// The hash that is published while the seed is still secret
+pub fn commitment(seed: &Seed) -> Seed {
+    Sha256::digest(seed).into()
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[test]
+fn test_seeded_rng() {
+    let seed = [0; 32];
+    assert_eq!(
+        to_hex(&commitment(&seed)),
+        "66687aadf862bd776c8fc18b8e9f8e20089714856ee233b3902a591d0d5f2925"
+    );
+
+    let rolls = |nonce| -> Vec<u32> {
+        let mut rng = rng(&seed, nonce);
+        (0..10).map(|_| die(&mut rng, 20)).collect()
+    };
+    assert_eq!(rolls(1), rolls(1));
+    assert_ne!(rolls(1), rolls(2));
+    assert!(rolls(3).iter().all(|roll| (1..=20).contains(roll)));
+}
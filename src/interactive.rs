@@ -1,80 +1,248 @@
-use crate::prelude::{serenity::ComponentInteraction, *};
-use poise::futures_util::StreamExt;
-use std::time::Duration;
+#[cfg(test)]
+pub mod fake;
+mod gateway;
+mod paginator;
+mod sessions;
+
+use crate::{data::Data, prelude::*};
+use serenity::Mentionable;
+use std::{collections::HashSet, time::Duration};
+use tokio::time::Instant;
+
+pub use gateway::{DiscordGateway, Gateway, Interaction, InteractionKind, Response};
+pub use paginator::Paginator;
+pub use sessions::{SessionInfo, Sessions};
 
 pub trait Interactive: Sized {
+    /// What kind of session this is, as shown in `/sessions list`
+    const KIND: &'static str;
+
+    /// Who may use the component or submit the modal with the given custom ID. Anyone else is
+    /// rejected before [`Interactive::process`] or [`Interactive::process_modal`] is called.
+    fn permission(&self, _custom_id: &str) -> Permission {
+        Permission::Everyone
+    }
+
     async fn run(
         &mut self,
         ctx: ApplicationContext<'_>,
         cfg: Config,
         initial_message: InteractiveMessage,
-    ) -> Result<serenity::MessageId> {
+    ) -> Result<Ended> {
         initial_message.run(ctx, cfg, self).await
     }
 
     async fn process(
         &mut self,
-        ctx: ApplicationContext<'_>,
-        interaction: &ComponentInteraction,
+        ctx: &SessionContext<'_>,
+        interaction: &Interaction,
     ) -> Result<ControlFlow>;
 
-    fn update(&mut self, ctx: ApplicationContext<'_>, message: &mut InteractiveMessage);
+    /// Called when a modal opened with [`ControlFlow::Modal`] is submitted
+    async fn process_modal(
+        &mut self,
+        _ctx: &SessionContext<'_>,
+        _interaction: &Interaction,
+    ) -> Result<ControlFlow> {
+        Ok(ControlFlow::Continue { update: false })
+    }
+
+    fn update(&mut self, ctx: &SessionContext<'_>, message: &mut InteractiveMessage);
 
     async fn finish(
         &mut self,
-        ctx: ApplicationContext<'_>,
+        ctx: &SessionContext<'_>,
         message: &mut InteractiveMessage,
+        reason: EndReason,
     ) -> Result<()>;
 }
 
 impl<T: Interactive> Interactive for &mut T {
+    const KIND: &'static str = T::KIND;
+
+    fn permission(&self, custom_id: &str) -> Permission {
+        T::permission(self, custom_id)
+    }
+
     async fn run(
         &mut self,
         ctx: ApplicationContext<'_>,
         cfg: Config,
         initial_message: InteractiveMessage,
-    ) -> Result<serenity::MessageId> {
+    ) -> Result<Ended> {
         initial_message.run::<T>(ctx, cfg, self).await
     }
 
     async fn process(
         &mut self,
-        ctx: ApplicationContext<'_>,
-        interaction: &ComponentInteraction,
+        ctx: &SessionContext<'_>,
+        interaction: &Interaction,
     ) -> Result<ControlFlow> {
         T::process(self, ctx, interaction).await
     }
 
-    fn update(&mut self, ctx: ApplicationContext<'_>, message: &mut InteractiveMessage) {
+    async fn process_modal(
+        &mut self,
+        ctx: &SessionContext<'_>,
+        interaction: &Interaction,
+    ) -> Result<ControlFlow> {
+        T::process_modal(self, ctx, interaction).await
+    }
+
+    fn update(&mut self, ctx: &SessionContext<'_>, message: &mut InteractiveMessage) {
         T::update(self, ctx, message);
     }
 
     async fn finish(
         &mut self,
-        ctx: ApplicationContext<'_>,
+        ctx: &SessionContext<'_>,
         message: &mut InteractiveMessage,
+        reason: EndReason,
     ) -> Result<()> {
-        T::finish(self, ctx, message).await
+        T::finish(self, ctx, message, reason).await
     }
 }
 
-#[derive(Default)]
+/// Everything a session can know about the command that started it, without depending on Discord
+pub struct SessionContext<'a> {
+    id: u64,
+    author_id: serenity::UserId,
+    channel_id: serenity::ChannelId,
+    owners: &'a HashSet<serenity::UserId>,
+    data: &'a Data,
+}
+
+impl<'a> SessionContext<'a> {
+    pub fn new(ctx: ApplicationContext<'a>) -> Self {
+        Self {
+            id: ctx.id(),
+            author_id: ctx.author().id,
+            channel_id: ctx.channel_id(),
+            owners: &ctx.framework().options().owners,
+            data: ctx.data(),
+        }
+    }
+
+    /// The ID of the command that started the session, which every custom ID starts with
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn author_id(&self) -> serenity::UserId {
+        self.author_id
+    }
+
+    pub fn channel_id(&self) -> serenity::ChannelId {
+        self.channel_id
+    }
+
+    pub fn data(&self) -> &'a Data {
+        self.data
+    }
+}
+
+#[derive(Default, Clone)]
 pub struct InteractiveMessage {
     pub content: Option<String>,
     pub embed: Option<serenity::CreateEmbed>,
     pub action_rows: Option<Vec<serenity::CreateActionRow>>,
+    /// Only sent with new messages. Edits keep the attachments that the message already has.
+    pub attachments: Vec<serenity::CreateAttachment>,
 }
 
 #[derive(Copy, Clone)]
 pub struct Config {
     pub duration: Duration,
+    /// The message is edited at most once per interval. Interactions in between are acknowledged
+    /// immediately and their changes are shown in the next edit.
+    pub update_interval: Duration,
 }
 
 pub enum ControlFlow {
-    Continue { update: bool },
+    /// Acknowledges the interaction, updating the message if `update` is true
+    Continue {
+        update: bool,
+    },
+    /// Responds with an ephemeral message that only the user can see
+    Ephemeral(InteractiveMessage),
+    /// Replaces the ephemeral message that the interaction came from
+    UpdateEphemeral(InteractiveMessage),
+    /// Responds with a new message that everyone can see
+    Reply(InteractiveMessage),
+    /// Responds to a component interaction with a modal. Its submission is passed to
+    /// [`Interactive::process_modal`], so its custom ID must start with the same prefix as the
+    /// session's components.
+    Modal(serenity::CreateInteractionResponse),
     Break,
 }
 
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum EndReason {
+    /// The session ended itself with [`ControlFlow::Break`]
+    Completed,
+    /// The session ran for its full duration
+    Expired,
+    /// A bot owner cancelled the session with `/sessions cancel`
+    Cancelled,
+    /// The bot is shutting down
+    Shutdown,
+}
+
+pub struct Ended {
+    pub message_id: serenity::MessageId,
+    pub reason: EndReason,
+}
+
+impl ControlFlow {
+    pub fn modal<M: poise::Modal>(custom_id: impl Into<String>, defaults: Option<M>) -> Self {
+        Self::Modal(M::create(defaults, custom_id.into()))
+    }
+
+    pub fn ephemeral(content: impl Into<String>) -> Self {
+        Self::Ephemeral(InteractiveMessage::new().content(content))
+    }
+}
+
+#[allow(unused)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Permission {
+    Everyone,
+    /// The person who started the session
+    Author,
+    /// The bot owners
+    Owners,
+    FrodgeMembers,
+    User(serenity::UserId),
+    Role(serenity::RoleId),
+}
+
+impl Permission {
+    fn allows(self, ctx: &SessionContext<'_>, interaction: &Interaction) -> bool {
+        let user_id = interaction.user_id;
+        match self {
+            Self::Everyone => true,
+            Self::Author => user_id == ctx.author_id,
+            Self::Owners => ctx.owners.contains(&user_id),
+            Self::FrodgeMembers => crate::is_frodge_member(user_id),
+            Self::User(id) => user_id == id,
+            Self::Role(role_id) => interaction.roles.contains(&role_id),
+        }
+    }
+
+    fn rejection_message(self) -> String {
+        match self {
+            Self::Everyone => unreachable!(),
+            Self::Author => String::from(":x: Only the person who started this can do that."),
+            Self::Owners => String::from(":x: Only the bot owners can do that."),
+            Self::FrodgeMembers => String::from(":x: Only Frodge members can do that."),
+            Self::User(id) => format!(":x: Only {} can do that.", id.mention()),
+            Self::Role(role_id) => {
+                format!(":x: Only members with {} can do that.", role_id.mention())
+            }
+        }
+    }
+}
+
 #[allow(unused)]
 impl InteractiveMessage {
     pub fn new() -> Self {
@@ -99,6 +267,11 @@ impl InteractiveMessage {
         self
     }
 
+    pub fn attachment(mut self, attachment: serenity::CreateAttachment) -> Self {
+        self.attachments.push(attachment);
+        self
+    }
+
     pub fn modify_content(&mut self, f: impl FnOnce(&mut String)) -> &mut Self {
         if let Some(content) = &mut self.content {
             f(content);
@@ -127,55 +300,120 @@ impl InteractiveMessage {
     }
 
     pub async fn run<T>(
-        mut self,
+        self,
         ctx: ApplicationContext<'_>,
         cfg: Config,
         interactive: &mut T,
-    ) -> Result<serenity::MessageId>
+    ) -> Result<Ended>
     where
         T: Interactive,
     {
-        let Config { duration } = cfg;
+        let session_ctx = SessionContext::new(ctx);
+        let mut gateway = DiscordGateway::new(ctx, cfg.duration);
+        self.run_with(&session_ctx, &mut gateway, cfg, interactive)
+            .await
+    }
 
-        let message = {
-            let response = self.create_message();
-            ctx.send(response).await?
-        };
+    /// Runs the session against any [`Gateway`], which lets sessions run without Discord
+    pub async fn run_with<T, G>(
+        mut self,
+        ctx: &SessionContext<'_>,
+        gateway: &mut G,
+        cfg: Config,
+        interactive: &mut T,
+    ) -> Result<Ended>
+    where
+        T: Interactive,
+        G: Gateway,
+    {
+        let Config {
+            duration,
+            update_interval,
+        } = cfg;
+
+        let message_id = gateway.send(&self).await?;
+        let (_session_guard, mut cancelled) =
+            ctx.data
+                .sessions()
+                .register(T::KIND, ctx.author_id, ctx.channel_id, duration);
 
-        let message_ref = message.message().await?;
-        let mut interaction_stream = message_ref
-            .as_ref()
-            .await_component_interactions(ctx)
-            .timeout(duration)
-            .stream();
+        let mut last_update: Option<Instant> = None;
+        // When the message should next be edited to show changes that haven't been shown yet
+        let mut pending_update: Option<Instant> = None;
 
-        while let Some(interaction) = interaction_stream.next().await {
-            match interactive.process(ctx, &interaction).await? {
-                ControlFlow::Continue { update: false } => {}
+        let reason = loop {
+            let interaction = tokio::select! {
+                interaction = gateway.next_interaction() => match interaction {
+                    Some(interaction) => interaction,
+                    None => break EndReason::Expired,
+                },
+                reason = &mut cancelled => break reason.unwrap_or(EndReason::Cancelled),
+                () = tokio::time::sleep_until(pending_update.unwrap_or_else(Instant::now)),
+                    if pending_update.is_some() =>
+                {
+                    gateway.edit(&self).await?;
+                    last_update = Some(Instant::now());
+                    pending_update = None;
+                    continue;
+                }
+            };
+
+            let permission = interactive.permission(&interaction.custom_id);
+            if !permission.allows(ctx, &interaction) {
+                let message = InteractiveMessage::new().content(permission.rejection_message());
+                gateway
+                    .respond(&interaction, Response::Ephemeral(message))
+                    .await?;
+                continue;
+            }
+
+            let is_modal = matches!(interaction.kind, InteractionKind::Modal(_));
+            let control_flow = if is_modal {
+                interactive.process_modal(ctx, &interaction).await?
+            } else {
+                interactive.process(ctx, &interaction).await?
+            };
+            let response = match control_flow {
+                ControlFlow::Continue { update: false } => Response::Acknowledge,
                 ControlFlow::Continue { update: true } => {
                     interactive.update(ctx, &mut self);
-                    let builder = self.response_update_message();
-                    interaction
-                        .create_response(
-                            ctx,
-                            serenity::CreateInteractionResponse::UpdateMessage(builder),
-                        )
-                        .await?;
+                    let now = Instant::now();
+                    let next_update = last_update.map(|last_update| last_update + update_interval);
+                    if pending_update.is_none() && next_update.is_none_or(|next| next <= now) {
+                        last_update = Some(now);
+                        Response::Update(self.clone())
+                    } else {
+                        pending_update.get_or_insert(next_update.unwrap_or(now));
+                        Response::Acknowledge
+                    }
+                }
+                ControlFlow::Ephemeral(message) => Response::Ephemeral(message),
+                ControlFlow::UpdateEphemeral(message) => Response::Update(message),
+                ControlFlow::Reply(message) => Response::Message(message),
+                // Discord doesn't allow this, so the session carries on without the modal
+                ControlFlow::Modal(_) if is_modal => {
+                    tracing::warn!("{} session tried to open a modal from a modal", T::KIND);
+                    let message = InteractiveMessage::new()
+                        .content(":x: Something went wrong. Please try again.");
+                    Response::Ephemeral(message)
                 }
+                ControlFlow::Modal(modal) => Response::Modal(modal),
                 ControlFlow::Break => {
-                    interaction
-                        .create_response(ctx, serenity::CreateInteractionResponse::Acknowledge)
-                        .await?;
-                    break;
+                    gateway.respond(&interaction, Response::Acknowledge).await?;
+                    break EndReason::Completed;
                 }
-            }
+            };
+            gateway.respond(&interaction, response).await?;
+        };
+
+        if pending_update.is_some() {
+            gateway.edit(&self).await?;
         }
 
-        interactive.finish(ctx, &mut self).await?;
-        let builder = self.edit_message();
-        message.edit(ctx.into(), builder).await?;
+        interactive.finish(ctx, &mut self, reason).await?;
+        gateway.edit(&self).await?;
 
-        Ok(message_ref.as_ref().id)
+        Ok(Ended { message_id, reason })
     }
 
     fn create_message(&self) -> poise::CreateReply {
@@ -189,6 +427,9 @@ impl InteractiveMessage {
         if let Some(action_rows) = self.action_rows.clone() {
             builder = builder.components(action_rows);
         }
+        for attachment in self.attachments.clone() {
+            builder = builder.attachment(attachment);
+        }
         builder
     }
 
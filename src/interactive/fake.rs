@@ -0,0 +1,177 @@
+//! Runs sessions without Discord, for tests
+
+use super::{Gateway, Interaction, InteractionKind, InteractiveMessage, Response, SessionContext};
+use crate::{data::Data, prelude::*};
+use shuttle_persist::PersistInstance;
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::OnceLock,
+};
+
+/// Plays back scripted interactions and records how the session responded to them.
+///
+/// The fake only has one message, so every [`Response::Update`] is applied to it.
+#[derive(Default)]
+pub struct FakeGateway {
+    interactions: VecDeque<Interaction>,
+    /// The session's message as it was last sent, updated or edited
+    pub message: Option<InteractiveMessage>,
+    pub responses: Vec<(serenity::UserId, Response)>,
+    pub edits: usize,
+}
+
+impl FakeGateway {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn click(&mut self, user_id: u64, custom_id: impl Into<String>) -> &mut Self {
+        self.push(user_id, custom_id, InteractionKind::Button)
+    }
+
+    /// Clicks a button as a member with the given roles
+    pub fn click_with_roles(
+        &mut self,
+        user_id: u64,
+        roles: &[u64],
+        custom_id: impl Into<String>,
+    ) -> &mut Self {
+        self.click(user_id, custom_id);
+        let interaction = self.interactions.back_mut().unwrap();
+        interaction.roles = roles.iter().map(|&id| serenity::RoleId::new(id)).collect();
+        self
+    }
+
+    pub fn select(
+        &mut self,
+        user_id: u64,
+        custom_id: impl Into<String>,
+        values: &[&str],
+    ) -> &mut Self {
+        let values = values.iter().map(|&value| String::from(value)).collect();
+        self.push(user_id, custom_id, InteractionKind::Select { values })
+    }
+
+    /// Submits a modal with the given values, keyed by the custom IDs of its inputs
+    pub fn submit(
+        &mut self,
+        user_id: u64,
+        custom_id: impl Into<String>,
+        values: &[(&str, &str)],
+    ) -> &mut Self {
+        let custom_id = custom_id.into();
+        let components: Vec<_> = values
+            .iter()
+            .map(|(input_id, value)| {
+                serde_json::json!({
+                    "type": 1,
+                    "components": [{ "type": 4, "custom_id": input_id, "value": value }],
+                })
+            })
+            .collect();
+        let data = serde_json::from_value(serde_json::json!({
+            "custom_id": custom_id,
+            "components": components,
+        }))
+        .unwrap();
+        self.push(user_id, custom_id, InteractionKind::Modal(data))
+    }
+
+    fn push(
+        &mut self,
+        user_id: u64,
+        custom_id: impl Into<String>,
+        kind: InteractionKind,
+    ) -> &mut Self {
+        self.interactions.push_back(Interaction {
+            user_id: serenity::UserId::new(user_id),
+            roles: Vec::new(),
+            custom_id: custom_id.into(),
+            kind,
+        });
+        self
+    }
+
+    /// The embed on the session's message, as JSON
+    pub fn embed(&self) -> serde_json::Value {
+        let embed = self
+            .message
+            .as_ref()
+            .and_then(|message| message.embed.as_ref());
+        serde_json::to_value(embed).unwrap()
+    }
+
+    /// How many ephemeral responses were sent, such as permission rejections and errors
+    pub fn ephemerals(&self) -> usize {
+        self.responses
+            .iter()
+            .filter(|(_, response)| matches!(response, Response::Ephemeral(_)))
+            .count()
+    }
+}
+
+impl Gateway for FakeGateway {
+    async fn send(&mut self, message: &InteractiveMessage) -> Result<serenity::MessageId> {
+        self.message = Some(message.clone());
+        Ok(serenity::MessageId::new(1))
+    }
+
+    async fn next_interaction(&mut self) -> Option<Interaction> {
+        self.interactions.pop_front()
+    }
+
+    async fn respond(&mut self, interaction: &Interaction, response: Response) -> Result {
+        if let Response::Update(message) = &response {
+            self.message = Some(message.clone());
+        }
+        self.responses.push((interaction.user_id, response));
+        Ok(())
+    }
+
+    async fn edit(&mut self, message: &InteractiveMessage) -> Result {
+        self.message = Some(message.clone());
+        self.edits += 1;
+        Ok(())
+    }
+}
+
+/// Data saved to a fresh temporary directory
+pub fn data() -> Data {
+    let dir = std::env::temp_dir().join(format!(
+        "patbot-test-{}-{:?}",
+        std::process::id(),
+        std::thread::current().id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    let persist = PersistInstance::new(dir).unwrap();
+    Data::load(persist, serenity::Color::BLURPLE)
+}
+
+/// A session started by `author_id`. Every custom ID in it starts with `1_`.
+pub fn session_context(data: &Data, author_id: u64) -> SessionContext<'_> {
+    static OWNERS: OnceLock<HashSet<serenity::UserId>> = OnceLock::new();
+
+    init_frodge_members();
+    SessionContext {
+        id: 1,
+        author_id: serenity::UserId::new(author_id),
+        channel_id: serenity::ChannelId::new(1),
+        owners: OWNERS.get_or_init(|| HashSet::from([serenity::UserId::new(1)])),
+        data,
+    }
+}
+
+/// Users 1 through 9 are the Frodge members
+fn init_frodge_members() {
+    crate::FRODGE_MEMBERS.get_or_init(|| {
+        (1..=9)
+            .map(|i| (format!("member{i}"), serenity::UserId::new(i)))
+            .collect()
+    });
+    crate::FRODGE_ROLES.get_or_init(|| {
+        (1..=9)
+            .map(|i| (serenity::RoleId::new(i), serenity::UserId::new(i)))
+            .collect()
+    });
+    crate::FRODGE_NONPREFERENTIAL_NAMES.get_or_init(HashSet::new);
+}
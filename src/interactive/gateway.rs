@@ -0,0 +1,186 @@
+use super::InteractiveMessage;
+use crate::prelude::*;
+use poise::futures_util::{
+    stream::{self, BoxStream},
+    StreamExt,
+};
+use std::time::Duration;
+
+/// The Discord side of an interactive session: sending its message, receiving interactions with
+/// it, responding to them and editing the message.
+pub trait Gateway {
+    async fn send(&mut self, message: &InteractiveMessage) -> Result<serenity::MessageId>;
+
+    /// Waits for the next interaction with the session. Returns `None` once the session times out.
+    async fn next_interaction(&mut self) -> Option<Interaction>;
+
+    async fn respond(&mut self, interaction: &Interaction, response: Response) -> Result;
+
+    async fn edit(&mut self, message: &InteractiveMessage) -> Result;
+}
+
+/// A component interaction or modal submission
+pub struct Interaction {
+    pub user_id: serenity::UserId,
+    pub roles: Vec<serenity::RoleId>,
+    pub custom_id: String,
+    pub kind: InteractionKind,
+}
+
+pub enum InteractionKind {
+    Button,
+    Select {
+        values: Vec<String>,
+    },
+    Modal(serenity::ModalInteractionData),
+    /// A kind of component that no session uses
+    Other,
+}
+
+pub enum Response {
+    Acknowledge,
+    /// Replaces the message that the interaction came from
+    Update(InteractiveMessage),
+    Ephemeral(InteractiveMessage),
+    /// A new message in the channel, which Discord shows as a reply to the interaction
+    Message(InteractiveMessage),
+    Modal(serenity::CreateInteractionResponse),
+}
+
+impl Response {
+    fn into_serenity_response(self) -> serenity::CreateInteractionResponse {
+        match self {
+            Self::Acknowledge => serenity::CreateInteractionResponse::Acknowledge,
+            Self::Update(message) => serenity::CreateInteractionResponse::UpdateMessage(
+                message.response_update_message(),
+            ),
+            Self::Ephemeral(message) => serenity::CreateInteractionResponse::Message(
+                message.response_update_message().ephemeral(true),
+            ),
+            Self::Message(message) => serenity::CreateInteractionResponse::Message(
+                message
+                    .response_update_message()
+                    .add_files(message.attachments),
+            ),
+            Self::Modal(modal) => modal,
+        }
+    }
+}
+
+enum Event {
+    Component(serenity::ComponentInteraction),
+    Modal(serenity::ModalInteraction),
+}
+
+impl Event {
+    fn to_interaction(&self) -> Interaction {
+        let roles = |member: Option<&serenity::Member>| {
+            member
+                .map(|member| member.roles.clone())
+                .unwrap_or_default()
+        };
+        match self {
+            Self::Component(interaction) => Interaction {
+                user_id: interaction.user.id,
+                roles: roles(interaction.member.as_ref()),
+                custom_id: interaction.data.custom_id.clone(),
+                kind: match &interaction.data.kind {
+                    serenity::ComponentInteractionDataKind::Button => InteractionKind::Button,
+                    serenity::ComponentInteractionDataKind::StringSelect { values } => {
+                        InteractionKind::Select {
+                            values: values.clone(),
+                        }
+                    }
+                    _ => InteractionKind::Other,
+                },
+            },
+            Self::Modal(interaction) => Interaction {
+                user_id: interaction.user.id,
+                roles: roles(interaction.member.as_ref()),
+                custom_id: interaction.data.custom_id.clone(),
+                kind: InteractionKind::Modal(interaction.data.clone()),
+            },
+        }
+    }
+}
+
+pub struct DiscordGateway<'a> {
+    ctx: ApplicationContext<'a>,
+    duration: Duration,
+    message: Option<poise::ReplyHandle<'a>>,
+    events: Option<BoxStream<'a, Event>>,
+    /// The interaction that hasn't been responded to yet
+    pending: Option<Event>,
+}
+
+impl<'a> DiscordGateway<'a> {
+    pub fn new(ctx: ApplicationContext<'a>, duration: Duration) -> Self {
+        Self {
+            ctx,
+            duration,
+            message: None,
+            events: None,
+            pending: None,
+        }
+    }
+}
+
+impl Gateway for DiscordGateway<'_> {
+    async fn send(&mut self, message: &InteractiveMessage) -> Result<serenity::MessageId> {
+        let ctx = self.ctx;
+        let handle = ctx.send(message.create_message()).await?;
+        let message_id = handle.message().await?.id;
+        self.message = Some(handle);
+
+        // Every component in a session has a custom ID starting with the ID of the command that
+        // started it. This also catches components on ephemeral messages sent during the session.
+        let custom_id_prefix = format!("{}_", ctx.id());
+        let component_stream = {
+            let custom_id_prefix = custom_id_prefix.clone();
+            serenity::ComponentInteractionCollector::new(ctx)
+                .filter(move |interaction| {
+                    interaction.data.custom_id.starts_with(&custom_id_prefix)
+                })
+                .timeout(self.duration)
+                .stream()
+                .map(Event::Component)
+        };
+        let modal_stream = serenity::ModalInteractionCollector::new(ctx)
+            .filter(move |interaction| interaction.data.custom_id.starts_with(&custom_id_prefix))
+            .timeout(self.duration)
+            .stream()
+            .map(Event::Modal);
+        self.events = Some(stream::select(component_stream, modal_stream).boxed());
+
+        Ok(message_id)
+    }
+
+    async fn next_interaction(&mut self) -> Option<Interaction> {
+        let event = self.events.as_mut()?.next().await?;
+        let interaction = event.to_interaction();
+        self.pending = Some(event);
+        Some(interaction)
+    }
+
+    async fn respond(&mut self, _interaction: &Interaction, response: Response) -> Result {
+        let Some(event) = self.pending.take() else {
+            return Err("the interaction was already responded to".into());
+        };
+        let response = response.into_serenity_response();
+        match event {
+            Event::Component(interaction) => {
+                interaction.create_response(self.ctx, response).await?
+            }
+            Event::Modal(interaction) => interaction.create_response(self.ctx, response).await?,
+        }
+        Ok(())
+    }
+
+    async fn edit(&mut self, message: &InteractiveMessage) -> Result {
+        let Some(handle) = &self.message else {
+            return Err("the message has not been sent yet".into());
+        };
+        handle.edit(self.ctx.into(), message.edit_message()).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,253 @@
+use super::{
+    Config, ControlFlow, EndReason, Interaction, InteractionKind, Interactive, InteractiveMessage,
+    SessionContext,
+};
+use crate::prelude::*;
+use std::{collections::HashMap, time::Duration};
+
+const PAGINATOR_DURATION: Duration = Duration::from_secs(60 * 10);
+const DESCRIPTION_LIMIT: usize = 4096;
+const MAX_SELECT_OPTIONS: usize = 25;
+
+/// Pages through long content with buttons and a jump-to-page menu.
+///
+/// Only the person who ran the command controls the original message. Anyone else who uses its
+/// components gets their own ephemeral view that they can page through independently.
+pub struct Paginator {
+    id: u64,
+    author: serenity::UserId,
+    title: String,
+    color: serenity::Color,
+    pages: Vec<String>,
+    footer: Option<String>,
+    page: usize,
+    views: HashMap<serenity::UserId, usize>,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum View {
+    /// The message sent by the command
+    Main,
+    /// An ephemeral message belonging to someone else
+    Personal,
+}
+
+impl View {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Main => "main",
+            Self::Personal => "view",
+        }
+    }
+}
+
+impl Paginator {
+    pub fn new(ctx: ApplicationContext<'_>, title: impl Into<String>, pages: Vec<String>) -> Self {
+        Self {
+            id: ctx.id(),
+            author: ctx.author().id,
+            title: title.into(),
+            color: ctx.data().bot_color(),
+            pages,
+            footer: None,
+            page: 0,
+            views: HashMap::default(),
+        }
+    }
+
+    /// Splits `lines` into pages of at most `lines_per_page` lines each
+    pub fn from_lines(
+        ctx: ApplicationContext<'_>,
+        title: impl Into<String>,
+        lines: impl IntoIterator<Item = String>,
+        lines_per_page: usize,
+    ) -> Self {
+        let mut pages = Vec::new();
+        let mut page = String::new();
+        let mut lines_on_page = 0;
+        for line in lines {
+            let too_long = page.len() + line.len() + 1 > DESCRIPTION_LIMIT;
+            if lines_on_page == lines_per_page || (lines_on_page > 0 && too_long) {
+                pages.push(std::mem::take(&mut page));
+                lines_on_page = 0;
+            }
+            if lines_on_page > 0 {
+                page.push('\n');
+            }
+            page.push_str(&line);
+            lines_on_page += 1;
+        }
+        if lines_on_page > 0 {
+            pages.push(page);
+        }
+        Self::new(ctx, title, pages)
+    }
+
+    /// Shows `footer` on every page, next to the page number
+    pub fn footer(mut self, footer: impl Into<String>) -> Self {
+        self.footer = Some(footer.into());
+        self
+    }
+
+    /// Sends the first page. Buttons are only added if there is more than one page.
+    pub async fn send(mut self, ctx: ApplicationContext<'_>) -> Result {
+        if self.pages.len() <= 1 {
+            ctx.send(poise::CreateReply::default().embed(self.embed(0)))
+                .await?;
+            return Ok(());
+        }
+
+        let message = InteractiveMessage::new()
+            .embed(self.embed(0))
+            .action_rows(self.action_rows(0, View::Main));
+        let config = Config {
+            duration: PAGINATOR_DURATION,
+            update_interval: Duration::ZERO,
+        };
+        self.run(ctx, config, message).await?;
+        Ok(())
+    }
+
+    fn last_page(&self) -> usize {
+        self.pages.len().saturating_sub(1)
+    }
+
+    fn embed(&self, page: usize) -> serenity::CreateEmbed {
+        let description = self
+            .pages
+            .get(page)
+            .cloned()
+            .unwrap_or_else(|| String::from("There is nothing here."));
+        let mut embed = serenity::CreateEmbed::default()
+            .color(self.color)
+            .title(self.title.clone())
+            .description(description);
+        let page_number =
+            (self.pages.len() > 1).then(|| format!("Page {}/{}", page + 1, self.pages.len()));
+        let footer = match (&self.footer, page_number) {
+            (Some(footer), Some(page_number)) => Some(format!("{footer} • {page_number}")),
+            (footer, page_number) => footer.clone().or(page_number),
+        };
+        if let Some(footer) = footer {
+            embed = embed.footer(serenity::CreateEmbedFooter::new(footer));
+        }
+        embed
+    }
+
+    fn custom_id(&self, view: View, name: &str) -> String {
+        format!("{}_page_{}_{name}", self.id, view.name())
+    }
+
+    fn action_rows(&self, page: usize, view: View) -> Vec<serenity::CreateActionRow> {
+        let last_page = self.last_page();
+        let button = |name: &str, emoji: &str, disabled: bool| {
+            serenity::CreateButton::new(self.custom_id(view, name))
+                .emoji(emoji.parse::<serenity::ReactionType>().unwrap())
+                .style(serenity::ButtonStyle::Secondary)
+                .disabled(disabled)
+        };
+        let buttons = vec![
+            button("first", "⏮️", page == 0),
+            button("prev", "◀️", page == 0),
+            button("next", "▶️", page == last_page),
+            button("last", "⏭️", page == last_page),
+        ];
+
+        // Discord limits select menus to 25 options, so only show the pages around this one
+        let start = page
+            .saturating_sub(MAX_SELECT_OPTIONS / 2)
+            .min(self.pages.len().saturating_sub(MAX_SELECT_OPTIONS));
+        let end = (start + MAX_SELECT_OPTIONS).min(self.pages.len());
+        let options = (start..end)
+            .map(|i| {
+                serenity::CreateSelectMenuOption::new(format!("Page {}", i + 1), i.to_string())
+                    .default_selection(i == page)
+            })
+            .collect();
+        let menu = serenity::CreateSelectMenu::new(
+            self.custom_id(view, "jump"),
+            serenity::CreateSelectMenuKind::String { options },
+        )
+        .placeholder("Jump to page");
+
+        vec![
+            serenity::CreateActionRow::Buttons(buttons),
+            serenity::CreateActionRow::SelectMenu(menu),
+        ]
+    }
+
+    fn navigate(&self, current: usize, interaction: &Interaction) -> usize {
+        let last_page = self.last_page();
+        match &interaction.kind {
+            InteractionKind::Select { values } => values
+                .first()
+                .and_then(|value| value.parse().ok())
+                .map_or(current, |page: usize| page.min(last_page)),
+            _ => match interaction.custom_id.rsplit_once('_') {
+                Some((_, "first")) => 0,
+                Some((_, "prev")) => current.saturating_sub(1),
+                Some((_, "next")) => (current + 1).min(last_page),
+                Some((_, "last")) => last_page,
+                _ => current,
+            },
+        }
+    }
+}
+
+impl Interactive for Paginator {
+    const KIND: &'static str = "paginator";
+
+    async fn process(
+        &mut self,
+        _ctx: &SessionContext<'_>,
+        interaction: &Interaction,
+    ) -> Result<ControlFlow> {
+        let user_id = interaction.user_id;
+        let view = if interaction
+            .custom_id
+            .starts_with(&self.custom_id(View::Main, ""))
+        {
+            View::Main
+        } else {
+            View::Personal
+        };
+
+        if view == View::Main && user_id == self.author {
+            self.page = self.navigate(self.page, interaction);
+            return Ok(ControlFlow::Continue { update: true });
+        }
+
+        let current = match view {
+            View::Main => self.page,
+            View::Personal => self.views.get(&user_id).copied().unwrap_or(self.page),
+        };
+        let page = self.navigate(current, interaction);
+        self.views.insert(user_id, page);
+
+        let message = InteractiveMessage::new()
+            .embed(self.embed(page))
+            .action_rows(self.action_rows(page, View::Personal));
+        Ok(match view {
+            View::Main => ControlFlow::Ephemeral(message),
+            View::Personal => ControlFlow::UpdateEphemeral(message),
+        })
+    }
+
+    fn update(&mut self, _ctx: &SessionContext<'_>, message: &mut InteractiveMessage) {
+        let embed = self.embed(self.page);
+        let action_rows = self.action_rows(self.page, View::Main);
+        message
+            .modify_embed(|_| embed)
+            .modify_action_rows(|_| action_rows);
+    }
+
+    async fn finish(
+        &mut self,
+        _ctx: &SessionContext<'_>,
+        message: &mut InteractiveMessage,
+        _reason: EndReason,
+    ) -> Result<()> {
+        message.modify_action_rows(|_| Vec::new());
+        Ok(())
+    }
+}
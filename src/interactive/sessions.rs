@@ -0,0 +1,125 @@
+use super::EndReason;
+use crate::prelude::*;
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+use time::OffsetDateTime;
+use tokio::{sync::oneshot, time::Instant};
+
+/// Every interactive session that is currently running
+#[derive(Default)]
+pub struct Sessions {
+    inner: Mutex<SessionsInner>,
+}
+
+#[derive(Default)]
+struct SessionsInner {
+    next_id: u32,
+    sessions: HashMap<u32, Session>,
+}
+
+struct Session {
+    info: SessionInfo,
+    /// Taken when the session is cancelled, so that it can't be cancelled twice
+    cancel: Option<oneshot::Sender<EndReason>>,
+}
+
+#[derive(Clone)]
+pub struct SessionInfo {
+    pub id: u32,
+    pub kind: &'static str,
+    pub author: serenity::UserId,
+    pub channel: serenity::ChannelId,
+    pub expires_at: OffsetDateTime,
+}
+
+/// Removes the session from the registry when it ends
+pub(super) struct SessionGuard<'a> {
+    sessions: &'a Sessions,
+    id: u32,
+}
+
+impl Drop for SessionGuard<'_> {
+    fn drop(&mut self) {
+        let mut inner = self.sessions.inner.lock().unwrap();
+        inner.sessions.remove(&self.id);
+    }
+}
+
+impl Sessions {
+    pub(super) fn register(
+        &self,
+        kind: &'static str,
+        author: serenity::UserId,
+        channel: serenity::ChannelId,
+        duration: std::time::Duration,
+    ) -> (SessionGuard<'_>, oneshot::Receiver<EndReason>) {
+        let (sender, receiver) = oneshot::channel();
+        let mut inner = self.inner.lock().unwrap();
+        inner.next_id += 1;
+        let id = inner.next_id;
+        let info = SessionInfo {
+            id,
+            kind,
+            author,
+            channel,
+            expires_at: OffsetDateTime::now_utc() + duration,
+        };
+        let session = Session {
+            info,
+            cancel: Some(sender),
+        };
+        inner.sessions.insert(id, session);
+        (SessionGuard { sessions: self, id }, receiver)
+    }
+
+    pub fn list(&self) -> Vec<SessionInfo> {
+        let inner = self.inner.lock().unwrap();
+        let mut sessions: Vec<_> = inner
+            .sessions
+            .values()
+            .map(|session| session.info.clone())
+            .collect();
+        sessions.sort_unstable_by_key(|info| info.id);
+        sessions
+    }
+
+    /// Returns false if there is no session with that ID, or it was already cancelled
+    pub fn cancel(&self, id: u32) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let Some(cancel) = inner
+            .sessions
+            .get_mut(&id)
+            .and_then(|session| session.cancel.take())
+        else {
+            return false;
+        };
+        cancel.send(EndReason::Cancelled).is_ok()
+    }
+
+    /// Ends every session, then waits for them to finish cleaning up their messages
+    pub async fn shutdown_all(&self) {
+        const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+        const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+        {
+            let mut inner = self.inner.lock().unwrap();
+            for session in inner.sessions.values_mut() {
+                if let Some(cancel) = session.cancel.take() {
+                    let _ = cancel.send(EndReason::Shutdown);
+                }
+            }
+        }
+
+        let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+        while !self.is_empty() {
+            if Instant::now() >= deadline {
+                tracing::warn!("timed out while waiting for sessions to shut down");
+                break;
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.inner.lock().unwrap().sessions.is_empty()
+    }
+}
@@ -17,24 +17,4 @@ macro_rules! reply_error {
     }};
 }
 
-macro_rules! respond_to_interaction {
-    ($ctx:ident, $interaction:ident) => {
-        $interaction.create_response(
-            $ctx,
-            crate::serenity::CreateInteractionResponse::Acknowledge,
-        )
-    };
-    ($ctx:ident, $interaction:ident, $message:expr) => {
-        $interaction.create_response(
-            $ctx,
-            crate::serenity::CreateInteractionResponse::Message(
-                crate::serenity::CreateInteractionResponseMessage::default()
-                    .content($message)
-                    .ephemeral(true),
-            ),
-        )
-    };
-}
-
 pub(crate) use reply_error;
-pub(crate) use respond_to_interaction;
@@ -1,5 +1,6 @@
 mod commands;
 mod data;
+mod dice;
 mod interactive;
 mod macros;
 
@@ -20,8 +21,7 @@ type ApplicationContext<'a> = poise::ApplicationContext<'a, Data, Error>;
 
 mod prelude {
     pub(crate) use crate::{
-        macros::{reply_error, respond_to_interaction},
-        serenity, ApplicationContext, Context, PatbotGuild, Result,
+        macros::reply_error, serenity, ApplicationContext, Context, PatbotGuild, Result,
     };
 }
 
@@ -179,7 +179,10 @@ async fn main(
             commands: vec![
                 commands::bonk(),
                 commands::bonk_context_menu(),
+                commands::character(),
                 commands::counter(),
+                commands::dice(),
+                commands::initiative(),
                 commands::petition(),
                 commands::ping(),
                 commands::poll(),
@@ -189,10 +192,15 @@ async fn main(
                 commands::roll(),
                 commands::roll_wildsea(),
                 commands::scatter(),
+                commands::sessions(),
                 commands::set_poll_mode(),
             ],
             event_handler: |ctx, event, framework, data| {
-                Box::pin(dynamic_command_handler(ctx, event, framework, data))
+                Box::pin(async move {
+                    dynamic_command_handler(ctx, event, framework, data).await?;
+                    commands::inline_rolls(ctx, event, data).await?;
+                    commands::reroll(ctx, event, data).await
+                })
             },
             ..Default::default()
         })